@@ -0,0 +1,235 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, anyhow};
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+
+/// A single entry in the tokens file
+#[derive(Deserialize)]
+struct TokenEntry {
+    token: String,
+    /// a human readable name for the holder of the token, used in logs and errors
+    name: Option<String>,
+    /// the tools this token may call. All tools are allowed when unset
+    tools: Option<Vec<String>>,
+    /// the sustained number of tool calls allowed per minute, at least 1. Unlimited when unset
+    requests_per_minute: Option<u32>,
+}
+
+/// What the holder of a bearer token is allowed to do
+pub struct TokenPolicy {
+    pub name: String,
+    tools: Option<HashSet<String>>,
+    limiter: Option<RateLimiter>,
+}
+
+impl TokenPolicy {
+    pub fn allows(&self, tool: &str) -> bool {
+        self.tools.as_ref().is_none_or(|tools| tools.contains(tool))
+    }
+
+    /// Takes one request from the token's rate limit, or returns how long until one is available
+    pub fn check_rate(&self) -> Result<(), Duration> {
+        match self.limiter.as_ref() {
+            Some(limiter) => limiter.acquire(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A token bucket holding up to a minute's worth of requests, refilled continuously
+struct RateLimiter {
+    per_minute: f64,
+    /// (available requests, last refill)
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u32) -> Self {
+        let per_minute = per_minute as f64;
+        Self {
+            per_minute,
+            state: Mutex::new((per_minute, Instant::now())),
+        }
+    }
+
+    fn acquire(&self) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(state.1).as_secs_f64() * self.per_minute / 60.0;
+        state.0 = (state.0 + refill).min(self.per_minute);
+        state.1 = now;
+        if state.0 >= 1.0 {
+            state.0 -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - state.0) * 60.0 / self.per_minute,
+            ))
+        }
+    }
+}
+
+/// The set of bearer tokens accepted by the HTTP transport
+pub struct Authenticator {
+    tokens: Vec<(String, Arc<TokenPolicy>)>,
+}
+
+impl Authenticator {
+    /// Loads tokens from the JSON tokens file, if any, plus plain tokens (e.g. from the
+    /// environment), which may call every tool without a rate limit
+    pub fn load(file: Option<&Path>, plain_tokens: &[String]) -> Result<Self, anyhow::Error> {
+        let mut entries: Vec<TokenEntry> = match file {
+            Some(file) => {
+                let data = fs::read_to_string(file)
+                    .with_context(|| format!("unable to read tokens file {}", file.display()))?;
+                serde_json::from_str(&data).context("unable to parse tokens file")?
+            }
+            None => Vec::new(),
+        };
+        entries.extend(plain_tokens.iter().map(|token| TokenEntry {
+            token: token.clone(),
+            name: None,
+            tools: None,
+            requests_per_minute: None,
+        }));
+        let tokens = entries
+            .into_iter()
+            .filter(|entry| !entry.token.is_empty())
+            .enumerate()
+            .map(|(index, entry)| {
+                let name = entry.name.unwrap_or_else(|| format!("token #{}", index));
+                // a token that may never make a request would wait forever, so it's a mistake
+                if entry.requests_per_minute == Some(0) {
+                    return Err(anyhow!(
+                        "requests_per_minute of {} must be at least 1, or unset for no limit",
+                        name
+                    ));
+                }
+                let policy = TokenPolicy {
+                    name,
+                    tools: entry.tools.map(|tools| tools.into_iter().collect()),
+                    limiter: entry.requests_per_minute.map(RateLimiter::new),
+                };
+                Ok((entry.token, Arc::new(policy)))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Self { tokens })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    fn authenticate(&self, authorization: Option<&HeaderValue>) -> Option<Arc<TokenPolicy>> {
+        let presented = authorization?.to_str().ok()?.strip_prefix("Bearer ")?;
+        // check every token, so the time taken doesn't reveal how close a guess was
+        let mut matched = None;
+        for (token, policy) in self.tokens.iter() {
+            if constant_time_eq(token.as_bytes(), presented.as_bytes()) {
+                matched = Some(policy.clone());
+            }
+        }
+        matched
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Middleware rejecting requests without a known bearer token. The matching [`TokenPolicy`] is
+/// added to the request extensions, so tool calls can check it
+pub async fn require_bearer(
+    State(auth): State<Arc<Authenticator>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match auth.authenticate(request.headers().get(header::AUTHORIZATION)) {
+        Some(policy) => {
+            request.extensions_mut().insert(policy);
+            next.run(request).await
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            "missing or invalid bearer token",
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokens_file() {
+        let path = std::env::temp_dir().join(format!("tokens-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"[
+                {"token": "abc", "name": "docs team", "tools": ["fetch_web_page"]},
+                {"token": "def", "requests_per_minute": 1}
+            ]"#,
+        )
+        .unwrap();
+        let auth = Authenticator::load(Some(&path), &["ghi".to_owned()]).unwrap();
+        fs::remove_file(path).unwrap();
+
+        let docs = auth
+            .authenticate(Some(&HeaderValue::from_static("Bearer abc")))
+            .unwrap();
+        assert_eq!(docs.name, "docs team");
+        assert!(docs.allows("fetch_web_page"));
+        assert!(!docs.allows("query_google_search"));
+
+        let limited = auth
+            .authenticate(Some(&HeaderValue::from_static("Bearer def")))
+            .unwrap();
+        assert!(limited.check_rate().is_ok());
+        assert!(limited.check_rate().is_err());
+
+        let plain = auth
+            .authenticate(Some(&HeaderValue::from_static("Bearer ghi")))
+            .unwrap();
+        assert!(plain.allows("query_google_search"));
+
+        assert!(
+            auth.authenticate(Some(&HeaderValue::from_static("Bearer ab")))
+                .is_none()
+        );
+        assert!(
+            auth.authenticate(Some(&HeaderValue::from_static("abc")))
+                .is_none()
+        );
+        assert!(auth.authenticate(None).is_none());
+    }
+
+    #[test]
+    fn test_zero_rate_rejected() {
+        let path = std::env::temp_dir().join(format!("tokens-zero-{}.json", std::process::id()));
+        fs::write(
+            &path,
+            r#"[{"token": "abc", "name": "ci", "requests_per_minute": 0}]"#,
+        )
+        .unwrap();
+        let err = Authenticator::load(Some(&path), &[]).err().unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(
+            err.to_string()
+                .contains("requests_per_minute of ci must be at least 1")
+        );
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::OwnedMutexGuard;
+use url::Url;
+
+/// The kind of upstream a cached response came from. Each source has its own TTL, since a
+/// Stack Overflow answer changes far less often than a Google result page
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CacheSource {
+    GoogleSearch,
+    StackOverflow,
+    Reddit,
+    HackerNews,
+    Discourse,
+    Crates,
+    GitHub,
+    Packages,
+    Scrapper,
+}
+
+/// Time-to-live for each cache source
+pub struct CacheTtls {
+    pub google_search: Duration,
+    pub stack_overflow: Duration,
+    pub reddit: Duration,
+    pub hacker_news: Duration,
+    pub discourse: Duration,
+    pub crates: Duration,
+    pub github: Duration,
+    pub packages: Duration,
+    pub scrapper: Duration,
+}
+
+impl CacheTtls {
+    fn for_source(&self, source: CacheSource) -> Duration {
+        match source {
+            CacheSource::GoogleSearch => self.google_search,
+            CacheSource::StackOverflow => self.stack_overflow,
+            CacheSource::Reddit => self.reddit,
+            CacheSource::HackerNews => self.hacker_news,
+            CacheSource::Discourse => self.discourse,
+            CacheSource::Crates => self.crates,
+            CacheSource::GitHub => self.github,
+            CacheSource::Packages => self.packages,
+            CacheSource::Scrapper => self.scrapper,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    source: CacheSource,
+    /// seconds since the unix epoch
+    stored_at: u64,
+    /// seconds since the unix epoch
+    expires_at: u64,
+    contents: Vec<String>,
+}
+
+/// An in-memory cache of tool responses, optionally persisted to disk so that results survive
+/// restarts (and so agent traces can be replayed without hitting upstreams again)
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    /// a lock per key being fetched, so concurrent misses on a key only fetch it once
+    in_flight: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+    ttls: CacheTtls,
+    max_entries: usize,
+    dir: Option<PathBuf>,
+}
+
+impl ResponseCache {
+    /// Creates the cache, loading any unexpired entries from `dir` if it is set.
+    /// A `max_entries` of 0 disables caching entirely
+    pub fn new(ttls: CacheTtls, max_entries: usize, dir: Option<PathBuf>) -> Self {
+        let cache = Self {
+            entries: Mutex::new(HashMap::new()),
+            in_flight: Arc::new(Mutex::new(HashMap::new())),
+            ttls,
+            max_entries,
+            dir,
+        };
+        if let Some(dir) = cache.dir.as_ref() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                eprintln!(
+                    "unable to create cache directory {}: {}",
+                    dir.display(),
+                    err
+                );
+            } else {
+                cache.load_from_disk();
+            }
+        }
+        cache
+    }
+
+    fn load_from_disk(&self) {
+        let dir = self.dir.as_ref().unwrap();
+        let Ok(files) = fs::read_dir(dir) else {
+            return;
+        };
+        let now = now_secs();
+        let mut entries = self.entries.lock().unwrap();
+        for file in files.flatten() {
+            let path = file.path();
+            if path.extension().is_none_or(|ext| ext != "json") {
+                continue;
+            }
+            let entry = fs::read_to_string(&path)
+                .ok()
+                .and_then(|data| serde_json::from_str::<CacheEntry>(&data).ok());
+            match entry {
+                Some(entry) if entry.expires_at > now => {
+                    entries.insert(entry.key.clone(), entry);
+                }
+                // expired or unreadable, either way it's of no use
+                _ => {
+                    let _ = fs::remove_file(&path);
+                }
+            }
+        }
+        drop(entries);
+        self.evict();
+    }
+
+    /// Returns the cached contents for `key` if present and not expired
+    pub fn get(&self, key: &str) -> Option<Vec<String>> {
+        if self.max_entries == 0 {
+            return None;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > now_secs() => Some(entry.contents.clone()),
+            Some(_) => {
+                entries.remove(key);
+                drop(entries);
+                self.remove_file(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Waits until no one else holds the lock for `key`, then holds it until the guard is
+    /// dropped
+    pub async fn lock(&self, key: &str) -> KeyGuard {
+        let lock = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.to_owned())
+            .or_default()
+            .clone();
+        KeyGuard {
+            guard: Some(lock.lock_owned().await),
+            key: key.to_owned(),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Stores `contents` under `key` with the TTL of `source`, evicting the oldest entries
+    /// if the cache is over capacity
+    pub fn put(&self, source: CacheSource, key: String, contents: Vec<String>) {
+        if self.max_entries == 0 {
+            return;
+        }
+        let stored_at = now_secs();
+        let entry = CacheEntry {
+            key: key.clone(),
+            source,
+            stored_at,
+            expires_at: stored_at + self.ttls.for_source(source).as_secs(),
+            contents,
+        };
+        if let Some(path) = self.file_path(&key) {
+            match serde_json::to_string(&entry) {
+                Ok(data) => {
+                    // written whole and then renamed, so concurrent writers and readers never
+                    // see a partial entry
+                    let tmp = path.with_extension(format!("{}.tmp", fastrand::u64(..)));
+                    if let Err(err) = fs::write(&tmp, data).and_then(|_| fs::rename(&tmp, &path)) {
+                        let _ = fs::remove_file(&tmp);
+                        eprintln!("unable to persist cache entry {}: {}", path.display(), err);
+                    }
+                }
+                Err(err) => eprintln!("unable to serialize cache entry: {}", err),
+            }
+        }
+        self.entries.lock().unwrap().insert(key, entry);
+        self.evict();
+    }
+
+    /// Drops expired entries, then the oldest entries until we're back under the size cap
+    fn evict(&self) {
+        let now = now_secs();
+        let mut entries = self.entries.lock().unwrap();
+        let mut removed: Vec<String> = entries
+            .values()
+            .filter(|entry| entry.expires_at <= now)
+            .map(|entry| entry.key.clone())
+            .collect();
+        for key in removed.iter() {
+            entries.remove(key);
+        }
+        if entries.len() > self.max_entries {
+            let mut by_age: Vec<(u64, String)> = entries
+                .values()
+                .map(|entry| (entry.stored_at, entry.key.clone()))
+                .collect();
+            by_age.sort();
+            let excess = entries.len() - self.max_entries;
+            for (_, key) in by_age.into_iter().take(excess) {
+                entries.remove(&key);
+                removed.push(key);
+            }
+        }
+        drop(entries);
+        for key in removed.iter() {
+            self.remove_file(key);
+        }
+    }
+
+    fn remove_file(&self, key: &str) {
+        if let Some(path) = self.file_path(key) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    fn file_path(&self, key: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{:016x}.json", fnv1a(key))))
+    }
+}
+
+/// Holds the lock on a cache key. The key's lock is forgotten once no one holds or waits for it
+pub struct KeyGuard {
+    guard: Option<OwnedMutexGuard<()>>,
+    key: String,
+    in_flight: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl Drop for KeyGuard {
+    fn drop(&mut self) {
+        drop(self.guard.take());
+        let mut in_flight = self.in_flight.lock().unwrap();
+        // the map holds one reference, so any more are waiters that still need it
+        if in_flight
+            .get(&self.key)
+            .is_some_and(|lock| Arc::strong_count(lock) == 1)
+        {
+            in_flight.remove(&self.key);
+        }
+    }
+}
+
+/// Builds a cache key from a list of parameters, normalizing whitespace and case so that
+/// trivially different queries share an entry. Unset parameters are skipped
+pub fn params_key(prefix: &str, params: &[(&str, Option<String>)]) -> String {
+    let mut key = prefix.to_owned();
+    for (name, value) in params.iter() {
+        if let Some(value) = value {
+            let normalized = value
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase();
+            key.push_str(&format!("|{}={}", name, normalized));
+        }
+    }
+    key
+}
+
+/// Reduces a URL to a canonical form for cache keys: no fragment, no tracking parameters,
+/// remaining query parameters sorted, and no trailing slash
+pub fn canonical_url(url: &Url) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+    let mut pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !name.starts_with("utm_"))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    pairs.sort();
+    if pairs.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    let mut canonical = url.to_string();
+    if canonical.ends_with('/') && url.query().is_none() {
+        canonical.pop();
+    }
+    canonical
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// FNV-1a, used for cache file names because it's stable across builds, unlike `DefaultHasher`
+fn fnv1a(data: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in data.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn ttls(secs: u64) -> CacheTtls {
+        CacheTtls {
+            google_search: Duration::from_secs(secs),
+            stack_overflow: Duration::from_secs(secs),
+            reddit: Duration::from_secs(secs),
+            hacker_news: Duration::from_secs(secs),
+            discourse: Duration::from_secs(secs),
+            crates: Duration::from_secs(secs),
+            github: Duration::from_secs(secs),
+            packages: Duration::from_secs(secs),
+            scrapper: Duration::from_secs(secs),
+        }
+    }
+
+    #[test]
+    fn test_canonical_url() {
+        let url = Url::from_str("https://Example.com/a/b/?utm_source=x&z=1&a=2#section").unwrap();
+        assert_eq!(canonical_url(&url), "https://example.com/a/b/?a=2&z=1");
+        let url = Url::from_str("https://example.com/a/b/#top").unwrap();
+        assert_eq!(canonical_url(&url), "https://example.com/a/b");
+    }
+
+    #[test]
+    fn test_params_key() {
+        let a = params_key(
+            "google",
+            &[("q", Some("Tokio  Spawn ".into())), ("start", None)],
+        );
+        let b = params_key(
+            "google",
+            &[("q", Some("tokio spawn".into())), ("start", None)],
+        );
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_cache_eviction_and_expiry() {
+        let cache = ResponseCache::new(ttls(60), 2, None);
+        cache.put(CacheSource::GoogleSearch, "a".into(), vec!["1".into()]);
+        cache.put(CacheSource::GoogleSearch, "b".into(), vec!["2".into()]);
+        assert_eq!(cache.get("a"), Some(vec!["1".to_owned()]));
+        // a third entry pushes one of the others out
+        cache.put(CacheSource::GoogleSearch, "c".into(), vec!["3".into()]);
+        assert_eq!(cache.entries.lock().unwrap().len(), 2);
+        assert!(cache.get("c").is_some());
+
+        let expired = ResponseCache::new(ttls(0), 2, None);
+        expired.put(CacheSource::Reddit, "a".into(), vec!["1".into()]);
+        assert_eq!(expired.get("a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_key_lock() {
+        let cache = ResponseCache::new(ttls(60), 10, None);
+        let guard = cache.lock("a").await;
+        let wait = Duration::from_millis(20);
+        assert!(tokio::time::timeout(wait, cache.lock("a")).await.is_err());
+        assert!(tokio::time::timeout(wait, cache.lock("b")).await.is_ok());
+        drop(guard);
+        assert!(tokio::time::timeout(wait, cache.lock("a")).await.is_ok());
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cache_persistence() {
+        let dir =
+            std::env::temp_dir().join(format!("coding-research-cache-{}", std::process::id()));
+        let cache = ResponseCache::new(ttls(60), 10, Some(dir.clone()));
+        cache.put(CacheSource::StackOverflow, "so:1".into(), vec!["q".into()]);
+        drop(cache);
+        let cache = ResponseCache::new(ttls(60), 10, Some(dir.clone()));
+        assert_eq!(cache.get("so:1"), Some(vec!["q".to_owned()]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, anyhow};
+use clap::ArgMatches;
+
+/// Maps `[section] key` in the config file to the id of the command line argument it sets
+pub const KEYS: &[(&str, &str, &str)] = &[
+    ("server", "transport", "transport"),
+    ("server", "listen", "listen"),
+    ("server", "worker_threads", "worker_threads"),
+    ("server", "auth_tokens", "auth_tokens"),
+    ("server", "auth_tokens_file", "auth_tokens_file"),
+    ("server", "allow_unauthenticated", "allow_unauthenticated"),
+    ("server", "user_agent", "user_agent"),
+    ("server", "contact", "contact"),
+    ("google", "search_engine_id", "google_search_engine_id"),
+    ("google", "api_key", "google_search_api_key"),
+    ("google", "daily_quota", "google_daily_quota"),
+    ("searxng", "url", "searxng_url"),
+    ("brave", "api_key", "brave_api_key"),
+    ("bing", "api_key", "bing_api_key"),
+    ("bing", "endpoint", "bing_endpoint"),
+    ("stackexchange", "api_prefix", "stack_overflow_api_prefix"),
+    ("stackexchange", "api_key", "stack_overflow_api_key"),
+    ("mdn", "base_path", "mdn_base_path"),
+    ("python", "docs_path", "python_docs_path"),
+    ("rust", "docs_path", "rust_docs_path"),
+    ("docsets", "path", "docsets_path"),
+    ("docsets", "urls", "docset_urls"),
+    ("man", "path", "man_path"),
+    ("man", "run_help", "man_run_help"),
+    ("reddit", "client_id", "reddit_client_id"),
+    ("reddit", "client_secret", "reddit_client_secret"),
+    ("reddit", "username", "reddit_username"),
+    ("reddit", "password", "reddit_password"),
+    ("reddit", "user_agent", "reddit_user_agent"),
+    ("discourse", "hosts", "discourse_hosts"),
+    ("github", "token", "github_token"),
+    ("scrapper", "host", "scrapper_host"),
+    ("search", "backends", "search_backends"),
+    ("search", "only_fetchable_results", "only_fetchable_results"),
+    ("search", "research_budget", "research_budget"),
+    ("cache", "dir", "cache_dir"),
+    ("cache", "max_entries", "cache_max_entries"),
+    ("cache", "ttl_google", "cache_ttl_google"),
+    ("cache", "ttl_stack_overflow", "cache_ttl_stack_overflow"),
+    ("cache", "ttl_reddit", "cache_ttl_reddit"),
+    ("cache", "ttl_hacker_news", "cache_ttl_hacker_news"),
+    ("cache", "ttl_discourse", "cache_ttl_discourse"),
+    ("cache", "ttl_crates", "cache_ttl_crates"),
+    ("cache", "ttl_github", "cache_ttl_github"),
+    ("cache", "ttl_packages", "cache_ttl_packages"),
+    ("cache", "ttl_scrapper", "cache_ttl_scrapper"),
+    ("http", "connect_timeout", "http_connect_timeout"),
+    ("http", "read_timeout", "http_read_timeout"),
+    ("http", "max_retries", "http_max_retries"),
+    ("http", "retry_max_delay", "http_retry_max_delay"),
+    ("http", "per_host_concurrency", "http_per_host_concurrency"),
+];
+
+/// Arguments holding secrets, which can also be read from a file named by the `<id>_file`
+/// argument, as Docker and Kubernetes secrets are mounted
+pub const SECRETS: &[&str] = &[
+    "google_search_api_key",
+    "stack_overflow_api_key",
+    "brave_api_key",
+    "bing_api_key",
+    "github_token",
+    "reddit_client_secret",
+    "reddit_password",
+];
+
+/// Collects the values that should be used for arguments not given as flags or env vars.
+///
+/// In order of precedence, lowest first: the config file at `path` (where any key may instead
+/// be given as `<key>_file` to read it from a file), then secrets read from the files named by
+/// `--<secret>-file` flags or env vars
+pub fn defaults(
+    path: Option<&Path>,
+    matches: &ArgMatches,
+) -> Result<Vec<(&'static str, String)>, anyhow::Error> {
+    let mut defaults = match path {
+        Some(path) => {
+            let data = fs::read_to_string(path)
+                .with_context(|| format!("unable to read config file {}", path.display()))?;
+            parse(&data).with_context(|| format!("invalid config file {}", path.display()))?
+        }
+        None => Vec::new(),
+    };
+    for secret in SECRETS.iter() {
+        if let Some(file) = matches
+            .try_get_one::<PathBuf>(&format!("{}_file", secret))
+            .ok()
+            .flatten()
+        {
+            defaults.retain(|(id, _)| id != secret);
+            defaults.push((secret, read_secret(file)?));
+        }
+    }
+    Ok(defaults)
+}
+
+fn parse(data: &str) -> Result<Vec<(&'static str, String)>, anyhow::Error> {
+    let table: toml::Table = toml::from_str(data)?;
+    let mut defaults = Vec::new();
+    for (section, keys) in table.iter() {
+        let keys = keys
+            .as_table()
+            .ok_or_else(|| anyhow!("[{}] must be a section", section))?;
+        for (key, value) in keys.iter() {
+            let (key, from_file) = match lookup(section, key) {
+                Some(id) => (id, false),
+                None => match key
+                    .strip_suffix("_file")
+                    .and_then(|key| lookup(section, key))
+                {
+                    Some(id) => (id, true),
+                    None => return Err(anyhow!("unknown key {} in [{}]", key, section)),
+                },
+            };
+            let value = to_arg_value(value)
+                .ok_or_else(|| anyhow!("unsupported value for {} in [{}]", key, section))?;
+            let value = if from_file {
+                read_secret(Path::new(&value))?
+            } else {
+                value
+            };
+            defaults.push((key, value));
+        }
+    }
+    Ok(defaults)
+}
+
+fn lookup(section: &str, key: &str) -> Option<&'static str> {
+    KEYS.iter()
+        .find(|(s, k, _)| *s == section && *k == key)
+        .map(|(_, _, id)| *id)
+}
+
+/// Converts a TOML value into the string clap would have parsed it from. Arrays become comma
+/// separated lists
+fn to_arg_value(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(s) => Some(s.clone()),
+        toml::Value::Integer(i) => Some(i.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        toml::Value::Array(values) => values
+            .iter()
+            .map(to_arg_value)
+            .collect::<Option<Vec<_>>>()
+            .map(|values| values.join(",")),
+        _ => None,
+    }
+}
+
+fn read_secret(path: &Path) -> Result<String, anyhow::Error> {
+    let secret = fs::read_to_string(path)
+        .with_context(|| format!("unable to read secret file {}", path.display()))?;
+    Ok(secret.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let secret = std::env::temp_dir().join(format!("secret-{}", std::process::id()));
+        fs::write(&secret, "hunter2\n").unwrap();
+        let defaults = parse(&format!(
+            r#"
+            [google]
+            search_engine_id = "cx"
+
+            [reddit]
+            password_file = "{}"
+
+            [server]
+            auth_tokens = ["a", "b"]
+
+            [cache]
+            max_entries = 10
+            "#,
+            secret.display()
+        ))
+        .unwrap();
+        fs::remove_file(secret).unwrap();
+        assert!(defaults.contains(&("google_search_engine_id", "cx".to_owned())));
+        assert!(defaults.contains(&("reddit_password", "hunter2".to_owned())));
+        assert!(defaults.contains(&("auth_tokens", "a,b".to_owned())));
+        assert!(defaults.contains(&("cache_max_entries", "10".to_owned())));
+
+        assert!(parse("[google]\nnope = 1").is_err());
+        assert!(parse("[nope]\napi_key = \"x\"").is_err());
+    }
+}
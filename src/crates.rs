@@ -0,0 +1,338 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    http::HttpClient,
+    passage::{html_to_markdown, truncate},
+};
+
+/// Item pages of large types (e.g. `Vec`) run to hundreds of kilobytes, mostly trait impls
+const MAX_DOCS_CHARS: usize = 40_000;
+
+/// How many of the most recent versions are listed
+const RECENT_VERSIONS: usize = 10;
+
+#[derive(Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: Crate,
+    versions: Vec<Version>,
+}
+
+#[derive(Deserialize)]
+struct Crate {
+    name: String,
+    description: Option<String>,
+    homepage: Option<String>,
+    repository: Option<String>,
+    documentation: Option<String>,
+    downloads: u64,
+    recent_downloads: Option<u64>,
+    max_stable_version: Option<String>,
+    max_version: String,
+    updated_at: String,
+}
+
+#[derive(Deserialize)]
+struct Version {
+    num: String,
+    yanked: bool,
+    created_at: String,
+    license: Option<String>,
+    rust_version: Option<String>,
+    #[serde(default)]
+    features: BTreeMap<String, Vec<String>>,
+}
+
+/// A crate, optionally pinned to a version and narrowed to an item, as in
+/// `serde@1.0.219::de::Deserialize`
+#[derive(Debug, PartialEq, Eq)]
+pub struct ItemSpec {
+    pub krate: String,
+    pub version: Option<String>,
+    pub path: Vec<String>,
+}
+
+impl ItemSpec {
+    pub fn parse(spec: &str) -> Option<Self> {
+        let mut parts = spec.trim().split("::");
+        let head = parts.next()?;
+        let (krate, version) = match head.split_once('@') {
+            Some((krate, version)) => (krate, Some(version.to_owned())),
+            None => (head, None),
+        };
+        // both go into docs.rs URLs, so only the characters crates.io allows are accepted
+        let valid_name = krate
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        let valid_version = version.as_deref().is_none_or(|version| {
+            version
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || ".+-".contains(c))
+        });
+        if krate.is_empty() || !valid_name || !valid_version {
+            return None;
+        }
+        let mut path: Vec<String> = parts.map(str::to_owned).collect();
+        // "tokio::tokio::sync" is as likely as "tokio::sync"
+        if path.first().is_some_and(|first| *first == ident(krate)) {
+            path.remove(0);
+        }
+        if path.iter().any(String::is_empty) {
+            return None;
+        }
+        Some(Self {
+            krate: krate.to_owned(),
+            version,
+            path,
+        })
+    }
+
+    fn version(&self) -> &str {
+        self.version.as_deref().unwrap_or("latest")
+    }
+
+    /// The root of the crate's documentation
+    fn root(&self) -> String {
+        format!(
+            "https://docs.rs/{}/{}/{}/",
+            self.krate,
+            self.version(),
+            ident(&self.krate)
+        )
+    }
+}
+
+/// The name a crate is referred to by in code
+fn ident(krate: &str) -> String {
+    krate.replace('-', "_")
+}
+
+/// The crate and version of a crates.io page, e.g. `/crates/serde` or `/crates/serde/1.0.219`
+pub fn crates_io_crate(url: &Url) -> Option<(String, Option<String>)> {
+    let mut segments = url.path_segments()?;
+    if segments.next()? != "crates" {
+        return None;
+    }
+    let krate = segments.next().filter(|krate| !krate.is_empty())?;
+    let version = segments
+        .next()
+        .filter(|version| !version.is_empty())
+        .map(str::to_owned);
+    Some((krate.to_owned(), version))
+}
+
+/// Fetches crate metadata from the crates.io API, describing `version` (or the latest stable
+/// version) in detail
+pub async fn fetch_crate(
+    http: &HttpClient,
+    krate: &str,
+    version: Option<&str>,
+) -> Result<String, anyhow::Error> {
+    let response: CrateResponse = http
+        .send(http.get(format!("https://crates.io/api/v1/crates/{}", krate)))
+        .await
+        .context("fetching crate from crates.io")?
+        .error_for_status()?
+        .json()
+        .await?;
+    render_crate(response, version)
+}
+
+fn render_crate(response: CrateResponse, version: Option<&str>) -> Result<String, anyhow::Error> {
+    let krate = response.krate;
+    let wanted = version
+        .or(krate.max_stable_version.as_deref())
+        .unwrap_or(&krate.max_version);
+    let selected = response
+        .versions
+        .iter()
+        .find(|v| v.num == wanted)
+        .ok_or_else(|| anyhow!("{} has no version {}", krate.name, wanted))?;
+
+    let mut text = format!("# {} {}\n\n", krate.name, selected.num);
+    if let Some(description) = krate.description.as_ref() {
+        let _ = writeln!(text, "{}\n", description.trim());
+    }
+    let _ = writeln!(
+        text,
+        "Latest stable: {}, newest: {}, updated {}",
+        krate.max_stable_version.as_deref().unwrap_or("none"),
+        krate.max_version,
+        krate.updated_at
+    );
+    let _ = writeln!(
+        text,
+        "Downloads: {} total, {} in the last 90 days",
+        krate.downloads,
+        krate.recent_downloads.unwrap_or(0)
+    );
+    for (label, link) in [
+        ("Repository", &krate.repository),
+        ("Homepage", &krate.homepage),
+        ("Documentation", &krate.documentation),
+    ] {
+        if let Some(link) = link {
+            let _ = writeln!(text, "{}: {}", label, link);
+        }
+    }
+    let _ = writeln!(
+        text,
+        "\n## Version {}\n\nReleased {}{}\nLicense: {}\nMSRV: {}",
+        selected.num,
+        selected.created_at,
+        if selected.yanked { " (yanked)" } else { "" },
+        selected.license.as_deref().unwrap_or("unknown"),
+        selected.rust_version.as_deref().unwrap_or("not declared")
+    );
+    if !selected.features.is_empty() {
+        let _ = writeln!(text, "\nFeatures:");
+        for (feature, enables) in selected.features.iter() {
+            let _ = writeln!(text, "- {} = [{}]", feature, enables.join(", "));
+        }
+    }
+    let _ = writeln!(text, "\n## Recent versions\n");
+    for version in response.versions.iter().take(RECENT_VERSIONS) {
+        let _ = writeln!(
+            text,
+            "- {} ({}){}{}",
+            version.num,
+            version.created_at.get(..10).unwrap_or(&version.created_at),
+            version
+                .rust_version
+                .as_ref()
+                .map(|msrv| format!(", MSRV {}", msrv))
+                .unwrap_or_default(),
+            if version.yanked { ", yanked" } else { "" }
+        );
+    }
+    Ok(text)
+}
+
+/// Fetches a docs.rs page and returns its main content as Markdown, without the navigation
+/// and search UI around it
+pub async fn fetch_docs_page(http: &HttpClient, url: &str) -> Result<String, anyhow::Error> {
+    let res = http
+        .send(http.get(url))
+        .await
+        .context("fetching docs.rs page")?
+        .error_for_status()?;
+    // docs.rs redirects "latest" to the actual version, which is worth knowing
+    let final_url = res.url().to_string();
+    let html = res.text().await?;
+    Ok(format!("{}\n\n{}", final_url, docs_content(&html)?))
+}
+
+pub fn docs_content(html: &str) -> Result<String, anyhow::Error> {
+    let start = html
+        .find("<section id=\"main-content\"")
+        .ok_or_else(|| anyhow!("not a rustdoc page"))?;
+    let end = html[start..]
+        .find("</main>")
+        .map_or(html.len(), |end| start + end);
+    Ok(truncate(
+        &html_to_markdown(&html[start..end]),
+        MAX_DOCS_CHARS,
+    ))
+}
+
+/// Finds the docs.rs URL of the item `spec` names. Item pages are named after the kind of
+/// item (e.g. `struct.Mutex.html`), so the crate's list of all items is searched for it,
+/// falling back to a module of that path
+pub async fn resolve_item(http: &HttpClient, spec: &ItemSpec) -> Result<String, anyhow::Error> {
+    let root = spec.root();
+    let Some((name, modules)) = spec.path.split_last() else {
+        return Ok(root);
+    };
+    let res = http
+        .send(http.get(format!("{}all.html", root)))
+        .await
+        .context("fetching docs.rs item list")?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+        return Err(anyhow!(
+            "docs.rs has no documentation for {}@{}",
+            spec.krate,
+            spec.version()
+        ));
+    }
+    let all = res.error_for_status()?.text().await?;
+    match find_item(&all, modules, name) {
+        Some(page) => Ok(format!("{}{}", root, page)),
+        None => Ok(format!("{}{}/index.html", root, spec.path.join("/"))),
+    }
+}
+
+/// The page of `modules::name` among the links of an `all.html` page
+pub fn find_item(all: &str, modules: &[String], name: &str) -> Option<String> {
+    let prefix = modules
+        .iter()
+        .map(|module| format!("{}/", module))
+        .collect::<String>();
+    all.split("href=\"").skip(1).find_map(|link| {
+        let page = &link[..link.find('"')?];
+        let file = page.strip_prefix(&prefix)?;
+        // e.g. "trait.Deserialize.html", but not "de/trait.Deserialize.html"
+        let (kind_and_name, _) = file.rsplit_once(".html")?;
+        let (_, item) = kind_and_name.split_once('.')?;
+        (!file.contains('/') && item == name).then(|| page.to_owned())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_item_spec() {
+        assert_eq!(
+            ItemSpec::parse("serde@1.0.219::de::Deserialize"),
+            Some(ItemSpec {
+                krate: "serde".into(),
+                version: Some("1.0.219".into()),
+                path: vec!["de".into(), "Deserialize".into()],
+            })
+        );
+        let spec = ItemSpec::parse("tokio-util::tokio_util::sync").unwrap();
+        assert_eq!(spec.path, ["sync"]);
+        assert_eq!(spec.root(), "https://docs.rs/tokio-util/latest/tokio_util/");
+        assert_eq!(ItemSpec::parse("::sync"), None);
+        assert_eq!(ItemSpec::parse("../../crates/x::sync"), None);
+        assert_eq!(ItemSpec::parse("serde@1.0/../x"), None);
+
+        let all = r#"<li><a href="de/trait.Deserialize.html">de::Deserialize</a></li>
+            <li><a href="trait.Deserialize.html">Deserialize</a></li>
+            <li><a href="de/value/struct.Error.html">de::value::Error</a></li>"#;
+        assert_eq!(
+            find_item(all, &["de".into()], "Deserialize").as_deref(),
+            Some("de/trait.Deserialize.html")
+        );
+        assert_eq!(
+            find_item(all, &[], "Deserialize").as_deref(),
+            Some("trait.Deserialize.html")
+        );
+        assert_eq!(find_item(all, &["de".into()], "Error"), None);
+    }
+
+    #[test]
+    fn test_render_crate() {
+        let response: CrateResponse =
+            serde_json::from_str(&fs::read_to_string("testdata/crates-io.json").unwrap()).unwrap();
+        let text = render_crate(response, None).unwrap();
+        assert!(text.starts_with("# serde 1.0.219"));
+        assert!(text.contains("MSRV: 1.31"));
+        assert!(text.contains("- derive = [serde_derive]"));
+        assert!(text.contains("- 1.0.218 (2025-02-20), MSRV 1.31, yanked"));
+
+        let html = r#"<html><nav>sidebar</nav><main><section id="main-content" class="content">
+            <h1>Struct <span>Mutex</span></h1><p>An asynchronous <code>Mutex</code>-like type.</p>
+            </section></main><footer>docs.rs</footer></html>"#;
+        let content = docs_content(html).unwrap();
+        assert!(content.contains("`Mutex`"));
+        assert!(!content.contains("sidebar") && !content.contains("footer"));
+    }
+}
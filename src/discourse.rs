@@ -0,0 +1,218 @@
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    http::HttpClient,
+    passage::html_to_markdown,
+    thread::{Comment, MAX_THREAD_CHARS, Thread},
+};
+
+/// The most posts of a topic returned. Long threads are cut off, as the first page of replies
+/// usually holds the answer
+const MAX_POSTS: usize = 60;
+
+/// How many posts Discourse returns per request
+const CHUNK_SIZE: usize = 20;
+
+/// The action type Discourse uses for likes in `actions_summary`
+const LIKE_ACTION: u32 = 2;
+
+#[derive(Deserialize)]
+struct Topic {
+    title: String,
+    posts_count: usize,
+    created_at: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// set by the Solved plugin
+    accepted_answer: Option<AcceptedAnswer>,
+    post_stream: PostStream,
+}
+
+#[derive(Deserialize)]
+struct AcceptedAnswer {
+    post_number: u64,
+}
+
+#[derive(Deserialize)]
+struct PostStream {
+    posts: Vec<Post>,
+    /// the ids of every post in the topic, including ones not loaded yet
+    #[serde(default)]
+    stream: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+struct Post {
+    id: u64,
+    username: String,
+    created_at: String,
+    /// the post rendered to HTML
+    cooked: String,
+    post_number: u64,
+    reply_to_post_number: Option<u64>,
+    #[serde(default)]
+    actions_summary: Vec<ActionSummary>,
+    #[serde(default)]
+    accepted_answer: bool,
+}
+
+#[derive(Deserialize)]
+struct ActionSummary {
+    id: u32,
+    #[serde(default)]
+    count: u64,
+}
+
+#[derive(Deserialize)]
+struct PostsResponse {
+    post_stream: PostStream,
+}
+
+/// The topic id of a Discourse topic URL, which is either `/t/{id}` or `/t/{slug}/{id}`,
+/// optionally followed by a post number
+pub fn topic_id(url: &Url) -> Option<u64> {
+    let mut segments = url.path_segments()?;
+    if segments.next()? != "t" {
+        return None;
+    }
+    let first = segments.next()?;
+    match first.parse() {
+        Ok(id) => Some(id),
+        Err(_) => segments.next()?.parse().ok(),
+    }
+}
+
+/// Fetches a topic through the JSON API, as the opening post followed by one part per reply
+pub async fn fetch_topic(
+    http: &HttpClient,
+    host: &str,
+    topic_id: u64,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut topic: Topic = http
+        .send(http.get(format!("https://{}/t/{}.json", host, topic_id)))
+        .await
+        .context("fetching discourse topic")?
+        .error_for_status()?
+        .json()
+        .await?;
+    // the topic only includes the first posts, the rest are fetched by id
+    let loaded: Vec<u64> = topic.post_stream.posts.iter().map(|post| post.id).collect();
+    let missing: Vec<u64> = topic
+        .post_stream
+        .stream
+        .iter()
+        .filter(|id| !loaded.contains(id))
+        .take(MAX_POSTS.saturating_sub(loaded.len()))
+        .copied()
+        .collect();
+    for chunk in missing.chunks(CHUNK_SIZE) {
+        let ids: Vec<_> = chunk
+            .iter()
+            .map(|id| ("post_ids[]", id.to_string()))
+            .collect();
+        let posts: PostsResponse = http
+            .send(
+                http.get(format!("https://{}/t/{}/posts.json", host, topic_id))
+                    .query(&ids),
+            )
+            .await
+            .context("fetching discourse posts")?
+            .error_for_status()?
+            .json()
+            .await?;
+        topic.post_stream.posts.extend(posts.post_stream.posts);
+    }
+    render(topic)
+}
+
+/// Renders the topic as a `Thread` of its replies, numbered by post number and marked if
+/// they're the accepted solution
+fn render(mut topic: Topic) -> Result<Vec<String>, anyhow::Error> {
+    topic.post_stream.posts.sort_by_key(|post| post.post_number);
+    let mut posts = topic.post_stream.posts.into_iter();
+    let first = posts
+        .next()
+        .ok_or_else(|| anyhow!("discourse returned a topic without posts"))?;
+    let accepted = topic.accepted_answer.map(|answer| answer.post_number);
+    let mut header = format!(
+        "# {}\n\nPosted {} by {}, {} likes, {} posts",
+        topic.title,
+        topic.created_at,
+        first.username,
+        likes(&first),
+        topic.posts_count
+    );
+    if !topic.tags.is_empty() {
+        header.push_str(&format!(", tags: {}", topic.tags.join(", ")));
+    }
+    if let Some(accepted) = accepted {
+        header.push_str(&format!(". Solved by post #{}", accepted));
+    }
+    let mut thread = Thread::new(
+        format!("{}\n\n{}", header, html_to_markdown(&first.cooked)),
+        MAX_THREAD_CHARS,
+    );
+    for post in posts {
+        let solution = if post.accepted_answer || accepted == Some(post.post_number) {
+            ", accepted solution"
+        } else {
+            ""
+        };
+        thread.push(Comment {
+            id: post.post_number as usize,
+            details: format!("{}, {} likes{}", post.created_at, likes(&post), solution),
+            author: post.username,
+            parent: post.reply_to_post_number.map(|parent| parent as usize),
+            body: html_to_markdown(&post.cooked),
+        });
+    }
+    Ok(thread.into_parts())
+}
+
+fn likes(post: &Post) -> u64 {
+    post.actions_summary
+        .iter()
+        .find(|action| action.id == LIKE_ACTION)
+        .map_or(0, |action| action.count)
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_render_topic() {
+        let mut topic: Topic =
+            serde_json::from_str(&fs::read_to_string("testdata/discourse-topic.json").unwrap())
+                .unwrap();
+        let posts: PostsResponse =
+            serde_json::from_str(&fs::read_to_string("testdata/discourse-posts.json").unwrap())
+                .unwrap();
+        topic.post_stream.posts.extend(posts.post_stream.posts);
+        let thread = render(topic).unwrap();
+        assert_eq!(thread.len(), 4);
+        assert!(thread[0].starts_with("# Efficiently embedding large static JSON data"));
+        assert!(thread[0].contains("by jane_doe, 2 likes, 4 posts, tags: serde"));
+        assert!(thread[0].contains("Solved by post #3"));
+        assert!(thread[0].contains("`include_str!`"));
+        assert!(!thread[0].contains("&amp;"));
+        assert!(thread[2].starts_with("<h1>Comment: #3 from kornel In response to: 1</h1>"));
+        assert!(thread[2].contains("7 likes, accepted solution"));
+        assert!(thread[3].contains("In response to: 3"));
+    }
+
+    #[test]
+    fn test_topic_id() {
+        let id = |url: &str| topic_id(&Url::parse(url).unwrap());
+        assert_eq!(
+            id("https://users.rust-lang.org/t/efficiently-embedding/116722/3"),
+            Some(116722)
+        );
+        assert_eq!(id("https://users.rust-lang.org/t/116722"), Some(116722));
+        assert_eq!(id("https://users.rust-lang.org/c/help/5"), None);
+    }
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+use url::Url;
+
+use crate::passage::{html_to_markdown, truncate};
+
+/// Reference pages like cppreference's std::basic_string run long, mostly member tables
+const MAX_DOCSET_CHARS: usize = 40_000;
+
+#[derive(Deserialize)]
+struct Index {
+    entries: Vec<IndexEntry>,
+}
+
+#[derive(Deserialize)]
+struct IndexEntry {
+    name: String,
+    /// the page, optionally with a fragment, e.g. "container/vector/push_back"
+    path: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+/// An entry of a docset's index, e.g. `std::vector::push_back` in the cpp docset
+struct Entry {
+    docset: String,
+    name: String,
+    kind: String,
+    path: String,
+}
+
+/// Doc bundles in the DevDocs format, as downloaded from devdocs.io or built with its scrapers:
+/// a directory per docset named by its slug (e.g. `cpp` or `python~3.12`), holding `index.json`,
+/// the docset's entries, and `db.json`, the HTML of its pages by path
+pub struct Docsets {
+    root: PathBuf,
+    /// the sites docsets were scraped from, by slug, so their URLs can be read locally
+    urls: HashMap<String, Url>,
+    /// the entries of every docset, read the first time one is searched
+    index: tokio::sync::OnceCell<Arc<Vec<Entry>>>,
+    /// the pages of each installed docset by slug, loaded when first read
+    pages: HashMap<String, tokio::sync::OnceCell<Arc<HashMap<String, String>>>>,
+}
+
+impl Docsets {
+    /// `urls` maps slugs to base URLs as "slug=url", e.g.
+    /// "cpp=https://en.cppreference.com/w/cpp/"
+    pub fn new(root: PathBuf, urls: &[String]) -> Result<Self, anyhow::Error> {
+        let urls = urls
+            .iter()
+            .map(|mapping| {
+                let (slug, url) = mapping
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("invalid docset URL {}: must be slug=url", mapping))?;
+                let url = Url::parse(url.trim())
+                    .with_context(|| format!("invalid docset URL for {}", slug))?;
+                Ok((slug.trim().to_owned(), url))
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        // the installed docsets are listed once, so URLs are matched without touching the disk
+        let pages = fs::read_dir(&root)
+            .context("unable to read the docsets directory")?
+            .flatten()
+            .filter(|docset| docset.file_type().is_ok_and(|file_type| file_type.is_dir()))
+            .filter_map(|docset| docset.file_name().into_string().ok())
+            .filter(|slug| !slug.starts_with('.'))
+            .map(|slug| (slug, tokio::sync::OnceCell::new()))
+            .collect();
+        Ok(Self {
+            root,
+            urls,
+            index: tokio::sync::OnceCell::new(),
+            pages,
+        })
+    }
+
+    /// The docset and page a URL refers to: either `devdocs.io/{slug}/{path}` for an installed
+    /// docset, or a URL under the base URL of one
+    pub fn page_path(&self, url: &Url) -> Option<(String, String)> {
+        let (slug, path) = if url.host_str() == Some("devdocs.io") {
+            let (slug, path) = url.path().trim_start_matches('/').split_once('/')?;
+            (slug.to_owned(), path.to_owned())
+        } else {
+            self.urls.iter().find_map(|(slug, base)| {
+                if url.host_str() != base.host_str() {
+                    return None;
+                }
+                let path = url.path().strip_prefix(base.path())?;
+                Some((slug.clone(), path.to_owned()))
+            })?
+        };
+        self.pages.contains_key(&slug).then_some((slug, path))
+    }
+
+    /// Reads the page of a devdocs.io URL or a URL of a docset's site. A fragment narrows the
+    /// page to the section it anchors
+    pub async fn page(&self, url: &Url) -> Result<String, anyhow::Error> {
+        let (slug, path) = self
+            .page_path(url)
+            .ok_or_else(|| anyhow!("no installed docset has the page {}", url))?;
+        let pages = self.pages(&slug).await?;
+        let path = path.trim_matches('/');
+        let candidates = if path.is_empty() {
+            vec!["index".to_owned()]
+        } else {
+            vec![
+                path.to_owned(),
+                path.trim_end_matches(".html").to_owned(),
+                path.to_lowercase(),
+                format!("{}/index", path),
+            ]
+        };
+        let html = candidates
+            .iter()
+            .find_map(|candidate| pages.get(candidate))
+            .ok_or_else(|| anyhow!("the {} docset has no page {}", slug, path))?;
+        let narrowed = url
+            .fragment()
+            .and_then(|fragment| fragment_html(html, fragment));
+        Ok(truncate(
+            &html_to_markdown(narrowed.unwrap_or(html)),
+            MAX_DOCSET_CHARS,
+        ))
+    }
+
+    /// Finds entries by name across the installed docsets, or only those of `docset` (a slug,
+    /// which matches all its versions, e.g. "python" matches "python~3.12"). Exact matches come
+    /// first, then names ending in the query, then names containing it
+    pub async fn search(
+        &self,
+        query: &str,
+        docset: Option<&str>,
+        num: usize,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let index = self
+            .index
+            .get_or_try_init(|| async {
+                let root = self.root.clone();
+                Ok::<_, anyhow::Error>(Arc::new(
+                    tokio::task::spawn_blocking(move || index_docsets(&root)).await??,
+                ))
+            })
+            .await?;
+        let query = query.trim().to_lowercase();
+        let mut matches: Vec<(u8, &Entry)> = index
+            .iter()
+            .filter(|entry| {
+                docset.is_none_or(|docset| {
+                    entry.docset == docset
+                        || entry
+                            .docset
+                            .strip_prefix(docset)
+                            .is_some_and(|version| version.starts_with('~'))
+                })
+            })
+            .filter_map(|entry| {
+                let name = entry.name.to_lowercase();
+                let rank = if name == query {
+                    0
+                } else if name.ends_with(&query)
+                    && !name[..name.len() - query.len()].ends_with(char::is_alphanumeric)
+                {
+                    1
+                } else if name.contains(&query) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, entry))
+            })
+            .collect();
+        matches.sort_by_key(|(rank, entry)| (*rank, entry.name.len(), entry.name.as_str()));
+        Ok(matches
+            .into_iter()
+            .take(num)
+            .map(|(_, entry)| {
+                format!(
+                    "{} ({}, {}) {}",
+                    entry.name,
+                    entry.kind,
+                    entry.docset,
+                    self.url(&entry.docset, &entry.path)
+                )
+            })
+            .collect())
+    }
+
+    /// The URL of a page: on the docset's site if it's known, otherwise on devdocs.io
+    fn url(&self, slug: &str, path: &str) -> String {
+        match self.urls.get(slug) {
+            Some(base) => format!("{}{}", base, path),
+            None => format!("https://devdocs.io/{}/{}", slug, path),
+        }
+    }
+
+    async fn pages(&self, slug: &str) -> Result<Arc<HashMap<String, String>>, anyhow::Error> {
+        let cell = self
+            .pages
+            .get(slug)
+            .ok_or_else(|| anyhow!("no installed docset {}", slug))?;
+        let docset = cell
+            .get_or_try_init(|| async {
+                let path = self.root.join(slug).join("db.json");
+                Ok::<_, anyhow::Error>(Arc::new(
+                    tokio::task::spawn_blocking(move || {
+                        let file = fs::File::open(&path)
+                            .with_context(|| format!("unable to read docset {}", path.display()))?;
+                        serde_json::from_reader::<_, HashMap<String, String>>(BufReader::new(file))
+                            .with_context(|| format!("invalid docset {}", path.display()))
+                    })
+                    .await??,
+                ))
+            })
+            .await?;
+        Ok(docset.clone())
+    }
+}
+
+fn index_docsets(root: &Path) -> Result<Vec<Entry>, anyhow::Error> {
+    let mut entries = Vec::new();
+    let docsets = fs::read_dir(root).context("unable to read the docsets directory")?;
+    for docset in docsets.flatten() {
+        let Ok(slug) = docset.file_name().into_string() else {
+            continue;
+        };
+        let Ok(file) = fs::File::open(docset.path().join("index.json")) else {
+            continue;
+        };
+        let index: Index = serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("invalid index of docset {}", slug))?;
+        entries.extend(index.entries.into_iter().map(|entry| Entry {
+            docset: slug.clone(),
+            name: entry.name,
+            kind: entry.kind,
+            path: entry.path,
+        }));
+    }
+    Ok(entries)
+}
+
+/// The part of a page anchored by `fragment`: a heading and what follows it up to the next
+/// heading of the same level, or for other elements, up to the next heading
+fn fragment_html<'a>(html: &'a str, fragment: &str) -> Option<&'a str> {
+    let anchor = html
+        .find(&format!("id=\"{}\"", fragment))
+        .or_else(|| html.find(&format!("id='{}'", fragment)))?;
+    let start = html[..anchor].rfind('<')?;
+    let level = match &html.as_bytes()[start + 1..] {
+        [b'h', level @ b'1'..=b'6', ..] => level - b'0',
+        _ => 6,
+    };
+    let end = html[start + 1..]
+        .match_indices("<h")
+        .find(|(index, _)| {
+            html.as_bytes()
+                .get(start + 1 + index + 2)
+                .is_some_and(|next| (b'1'..=b'0' + level).contains(next))
+        })
+        .map_or(html.len(), |(index, _)| start + 1 + index);
+    Some(&html[start..end])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn docsets() -> Docsets {
+        Docsets::new(
+            PathBuf::from("testdata/docsets"),
+            &[
+                "c=https://en.cppreference.com/w/c/".to_owned(),
+                "cpp=https://en.cppreference.com/w/cpp/".to_owned(),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_page() {
+        let docsets = docsets();
+        let page = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            let docsets = &docsets;
+            async move { docsets.page(&url).await }
+        };
+        let text = page("https://en.cppreference.com/w/cpp/container/vector")
+            .await
+            .unwrap();
+        assert!(text.starts_with("# std::vector"));
+        assert!(text.contains("push_back"));
+        let text = page("https://devdocs.io/cpp/container/vector#Iterator_invalidation")
+            .await
+            .unwrap();
+        assert!(text.starts_with("## Iterator invalidation"));
+        assert!(!text.contains("Member functions"));
+        let text = page("https://devdocs.io/cpp/container/vector/push_back")
+            .await
+            .unwrap();
+        assert!(text.contains("Appends the given element"));
+        assert!(page("https://devdocs.io/cpp/container/map").await.is_err());
+        assert!(page("https://devdocs.io/go/fmt").await.is_err());
+        // the c docset isn't installed
+        assert!(
+            docsets
+                .page_path(&Url::parse("https://en.cppreference.com/w/c/io/fprintf").unwrap())
+                .is_none()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let docsets = docsets();
+        let results = docsets.search("push_back", None, 5).await.unwrap();
+        assert_eq!(
+            results,
+            [
+                "std::vector::push_back (Containers, cpp) https://en.cppreference.com/w/cpp/container/vector/push_back"
+            ]
+        );
+        let results = docsets.search("vector", Some("cpp"), 5).await.unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results[0].starts_with("std::vector (Containers, cpp)"));
+        let results = docsets.search("join", Some("python"), 5).await.unwrap();
+        assert_eq!(
+            results,
+            [
+                "os.path.join (os.path, python~3.12) https://devdocs.io/python~3.12/library/os.path#os.path.join"
+            ]
+        );
+        assert!(
+            docsets
+                .search("join", Some("cpp"), 5)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,978 @@
+use std::fmt::Write;
+
+use anyhow::{Context, anyhow};
+use reqwest::{
+    RequestBuilder, StatusCode,
+    header::{ACCEPT, AUTHORIZATION},
+};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::json;
+use url::Url;
+
+use crate::{http::HttpClient, passage::truncate};
+
+const API: &str = "https://api.github.com";
+
+/// The most characters of a page returned. The opening post gets up to half, comments share
+/// the rest in order of reactions
+const MAX_GITHUB_CHARS: usize = 40_000;
+
+/// Top level paths of github.com that are GitHub's own pages rather than users or
+/// organizations, e.g. `github.com/features/actions`
+const RESERVED_OWNERS: &[&str] = &[
+    "about",
+    "account",
+    "apps",
+    "codespaces",
+    "collections",
+    "contact",
+    "customer-stories",
+    "enterprise",
+    "events",
+    "explore",
+    "features",
+    "issues",
+    "login",
+    "marketplace",
+    "new",
+    "notifications",
+    "orgs",
+    "organizations",
+    "pricing",
+    "pulls",
+    "search",
+    "security",
+    "settings",
+    "site",
+    "solutions",
+    "sponsors",
+    "topics",
+    "trending",
+    "users",
+];
+
+/// The GitHub pages we know how to fetch through the API
+#[derive(Debug, PartialEq, Eq)]
+pub enum GitHubPage {
+    Repo {
+        owner: String,
+        repo: String,
+    },
+    Issue {
+        owner: String,
+        repo: String,
+        number: u64,
+    },
+    Pull {
+        owner: String,
+        repo: String,
+        number: u64,
+    },
+    Discussion {
+        owner: String,
+        repo: String,
+        number: u64,
+    },
+    /// a file at a branch, tag or commit, optionally narrowed to a range of lines
+    Blob {
+        owner: String,
+        repo: String,
+        reference: String,
+        path: String,
+        lines: Option<(usize, usize)>,
+    },
+}
+
+impl GitHubPage {
+    /// Parses github.com URLs of repos, issues, pull requests, discussions and files, and
+    /// raw.githubusercontent.com URLs of files. Other pages, e.g. a repo's settings or a user's
+    /// profile, aren't supported. Branch names containing a slash can't be told apart from the
+    /// path, so only the first segment is taken as the branch
+    pub fn parse(url: &Url) -> Option<Self> {
+        let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+        let owner = segments.first()?.to_string();
+        let repo = segments.get(1)?.to_string();
+        if url.host_str() == Some("github.com")
+            && RESERVED_OWNERS.contains(&owner.to_lowercase().as_str())
+        {
+            return None;
+        }
+        let blob = |rest: &[&str]| {
+            let (reference, path) = rest.split_first()?;
+            (!path.is_empty()).then(|| GitHubPage::Blob {
+                owner: owner.clone(),
+                repo: repo.clone(),
+                reference: reference.to_string(),
+                path: path.join("/"),
+                lines: url.fragment().and_then(line_range),
+            })
+        };
+        if url.host_str() == Some("raw.githubusercontent.com") {
+            return blob(&segments[2..]);
+        }
+        let number = || segments.get(3)?.parse().ok();
+        match segments.get(2).copied() {
+            None => Some(GitHubPage::Repo { owner, repo }),
+            Some("issues") => Some(GitHubPage::Issue {
+                owner,
+                repo,
+                number: number()?,
+            }),
+            Some("pull") => Some(GitHubPage::Pull {
+                owner,
+                repo,
+                number: number()?,
+            }),
+            Some("discussions") => Some(GitHubPage::Discussion {
+                owner,
+                repo,
+                number: number()?,
+            }),
+            Some("blob") => blob(&segments[3..]),
+            Some(_) => None,
+        }
+    }
+
+    /// A short stable name for the page, used as its cache key
+    pub fn key(&self) -> String {
+        match self {
+            GitHubPage::Repo { owner, repo } => format!("{}/{}", owner, repo),
+            GitHubPage::Issue {
+                owner,
+                repo,
+                number,
+            }
+            | GitHubPage::Pull {
+                owner,
+                repo,
+                number,
+            } => format!("{}/{}#{}", owner, repo, number),
+            GitHubPage::Discussion {
+                owner,
+                repo,
+                number,
+            } => format!("{}/{}/discussions/{}", owner, repo, number),
+            GitHubPage::Blob {
+                owner,
+                repo,
+                reference,
+                path,
+                lines,
+            } => {
+                let mut key = format!("{}/{}@{}:{}", owner, repo, reference, path);
+                if let Some((start, end)) = lines {
+                    let _ = write!(key, "#L{}-L{}", start, end);
+                }
+                key
+            }
+        }
+    }
+}
+
+/// The lines a `#L10-L40` or `#L10` fragment selects
+fn line_range(fragment: &str) -> Option<(usize, usize)> {
+    let (start, end) = match fragment.split_once('-') {
+        Some((start, end)) => (start, end),
+        None => (fragment, fragment),
+    };
+    let start: usize = start.strip_prefix('L')?.parse().ok()?;
+    let end: usize = end.strip_prefix('L')?.parse().ok()?;
+    (start > 0 && start <= end).then_some((start, end))
+}
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+}
+
+#[derive(Deserialize, Default)]
+struct Reactions {
+    #[serde(default)]
+    total_count: u64,
+}
+
+#[derive(Deserialize)]
+struct Label {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct Issue {
+    number: u64,
+    title: String,
+    state: String,
+    state_reason: Option<String>,
+    user: User,
+    #[serde(default)]
+    labels: Vec<Label>,
+    body: Option<String>,
+    created_at: String,
+    #[serde(default)]
+    reactions: Reactions,
+}
+
+#[derive(Deserialize)]
+struct Pull {
+    merged: bool,
+    merged_at: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    head: Branch,
+    base: Branch,
+    additions: u64,
+    deletions: u64,
+    changed_files: u64,
+}
+
+#[derive(Deserialize)]
+struct Branch {
+    label: String,
+}
+
+#[derive(Deserialize)]
+struct Comment {
+    user: User,
+    body: Option<String>,
+    created_at: String,
+    #[serde(default)]
+    reactions: Reactions,
+}
+
+#[derive(Deserialize)]
+struct TimelineEvent {
+    event: Option<String>,
+    source: Option<TimelineSource>,
+}
+
+#[derive(Deserialize)]
+struct TimelineSource {
+    issue: Option<LinkedIssue>,
+}
+
+#[derive(Deserialize)]
+struct LinkedIssue {
+    title: String,
+    state: String,
+    html_url: String,
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct Repo {
+    full_name: String,
+    description: Option<String>,
+    stargazers_count: u64,
+    forks_count: u64,
+    open_issues_count: u64,
+    license: Option<License>,
+    default_branch: String,
+    #[serde(default)]
+    topics: Vec<String>,
+    homepage: Option<String>,
+    pushed_at: String,
+    #[serde(default)]
+    archived: bool,
+}
+
+#[derive(Deserialize)]
+struct License {
+    spdx_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+    name: Option<String>,
+    published_at: Option<String>,
+    body: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct CodeSearch {
+    total_count: u64,
+    items: Vec<CodeResult>,
+}
+
+#[derive(Deserialize)]
+struct CodeResult {
+    path: String,
+    html_url: String,
+    repository: CodeRepo,
+    #[serde(default)]
+    text_matches: Vec<TextMatch>,
+}
+
+#[derive(Deserialize)]
+struct CodeRepo {
+    full_name: String,
+}
+
+#[derive(Deserialize)]
+struct TextMatch {
+    fragment: String,
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct DiscussionData {
+    repository: Option<DiscussionRepo>,
+}
+
+#[derive(Deserialize)]
+struct DiscussionRepo {
+    discussion: Option<Discussion>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Discussion {
+    title: String,
+    body: String,
+    created_at: String,
+    upvote_count: u64,
+    author: Option<User>,
+    category: Option<DiscussionCategory>,
+    answer: Option<DiscussionAnswer>,
+    comments: DiscussionComments,
+}
+
+#[derive(Deserialize)]
+struct DiscussionCategory {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct DiscussionAnswer {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DiscussionComments {
+    nodes: Vec<DiscussionComment>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscussionComment {
+    id: String,
+    body: String,
+    created_at: String,
+    upvote_count: u64,
+    author: Option<User>,
+}
+
+const DISCUSSION_QUERY: &str = "query($owner: String!, $repo: String!, $number: Int!) {
+  repository(owner: $owner, name: $repo) {
+    discussion(number: $number) {
+      title body createdAt upvoteCount
+      author { login }
+      category { name }
+      answer { id }
+      comments(first: 50) {
+        nodes { id body createdAt upvoteCount author { login } }
+      }
+    }
+  }
+}";
+
+/// The GitHub API, authenticated with a token if one is configured. Without one, GitHub
+/// allows 60 requests an hour and discussions can't be fetched at all
+pub struct GitHub<'a> {
+    pub http: &'a HttpClient,
+    pub token: Option<&'a str>,
+}
+
+impl GitHub<'_> {
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.get_as(path, "application/vnd.github+json")
+    }
+
+    /// A GET request for `path`, with the response in the media type `accept`
+    fn get_as(&self, path: &str, accept: &str) -> RequestBuilder {
+        self.authorize(self.http.get(format!("{}{}", API, path)), accept)
+    }
+
+    fn authorize(&self, request: RequestBuilder, accept: &str) -> RequestBuilder {
+        let request = request
+            .header(ACCEPT, accept)
+            .header("X-GitHub-Api-Version", "2022-11-28");
+        match self.token {
+            Some(token) => request.header(AUTHORIZATION, format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<reqwest::Response, anyhow::Error> {
+        let res = self
+            .http
+            .send(request)
+            .await
+            .context("sending GitHub API request")?;
+        let status = res.status();
+        if status.is_success() {
+            return Ok(res);
+        }
+        let out_of_quota = res
+            .headers()
+            .get("x-ratelimit-remaining")
+            .is_some_and(|remaining| remaining == "0");
+        if out_of_quota || status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(anyhow!(
+                "the GitHub API rate limit is used up{}",
+                if self.token.is_none() {
+                    ", set --github-token to raise it"
+                } else {
+                    ""
+                }
+            ));
+        }
+        let body: String = res
+            .text()
+            .await
+            .unwrap_or_default()
+            .chars()
+            .take(200)
+            .collect();
+        Err(anyhow!("GitHub API request failed ({}): {}", status, body))
+    }
+
+    async fn json<T: DeserializeOwned>(&self, path: &str) -> Result<T, anyhow::Error> {
+        Ok(self.send(self.get(path)).await?.json().await?)
+    }
+
+    pub async fn fetch(&self, page: &GitHubPage) -> Result<Vec<String>, anyhow::Error> {
+        match page {
+            GitHubPage::Repo { owner, repo } => self.fetch_repo(owner, repo).await,
+            GitHubPage::Issue {
+                owner,
+                repo,
+                number,
+            } => self.fetch_issue(owner, repo, *number, false).await,
+            GitHubPage::Pull {
+                owner,
+                repo,
+                number,
+            } => self.fetch_issue(owner, repo, *number, true).await,
+            GitHubPage::Discussion {
+                owner,
+                repo,
+                number,
+            } => self.fetch_discussion(owner, repo, *number).await,
+            GitHubPage::Blob {
+                owner,
+                repo,
+                reference,
+                path,
+                lines,
+            } => self.fetch_blob(owner, repo, reference, path, *lines).await,
+        }
+    }
+
+    /// Fetches a file from raw.githubusercontent.com, which doesn't count against the API rate
+    /// limit
+    async fn fetch_blob(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        path: &str,
+        lines: Option<(usize, usize)>,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let mut request = self.http.get(format!(
+            "https://raw.githubusercontent.com/{}/{}/{}/{}",
+            owner, repo, reference, path
+        ));
+        if let Some(token) = self.token {
+            request = request.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let res = self
+            .http
+            .send(request)
+            .await
+            .context("fetching file from GitHub")?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Err(anyhow!(
+                "{}/{} has no file {} at {}",
+                owner,
+                repo,
+                path,
+                reference
+            ));
+        }
+        let source = res.error_for_status()?.text().await?;
+        Ok(vec![render_blob(
+            &format!("{}/{}@{}", owner, repo, reference),
+            path,
+            &source,
+            lines,
+        )])
+    }
+
+    /// Searches code on GitHub, which requires a token. Each result is a file with the
+    /// fragments matching the query
+    pub async fn search_code(&self, query: &str, num: u8) -> Result<Vec<String>, anyhow::Error> {
+        if self.token.is_none() {
+            return Err(anyhow!(
+                "GitHub code search requires a token, set --github-token"
+            ));
+        }
+        let results: CodeSearch = self
+            .send(
+                self.get_as("/search/code", "application/vnd.github.text-match+json")
+                    .query(&[("q", query), ("per_page", &num.to_string())]),
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(render_code_search(results))
+    }
+
+    /// Issues and pull requests share the issues API. Pull requests add merge state and diff
+    /// stats, issues the pull requests that reference them
+    async fn fetch_issue(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        is_pull: bool,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let base = format!("/repos/{}/{}", owner, repo);
+        let issue: Issue = self.json(&format!("{}/issues/{}", base, number)).await?;
+        let comments: Vec<Comment> = self
+            .json(&format!("{}/issues/{}/comments?per_page=100", base, number))
+            .await?;
+        let details = if is_pull {
+            let pull: Pull = self.json(&format!("{}/pulls/{}", base, number)).await?;
+            pull_details(&pull)
+        } else {
+            let timeline: Vec<TimelineEvent> = self
+                .json(&format!("{}/issues/{}/timeline?per_page=100", base, number))
+                .await
+                .unwrap_or_default();
+            linked_pulls(timeline)
+        };
+        Ok(render_issue(
+            &format!("{}/{}", owner, repo),
+            issue,
+            details,
+            comments,
+            is_pull,
+        ))
+    }
+
+    async fn fetch_repo(&self, owner: &str, repo: &str) -> Result<Vec<String>, anyhow::Error> {
+        let base = format!("/repos/{}/{}", owner, repo);
+        let info: Repo = self.json(&base).await?;
+        let mut summary = format!("# {}\n\n", info.full_name);
+        if let Some(description) = info.description.as_ref() {
+            let _ = writeln!(summary, "{}\n", description);
+        }
+        let _ = writeln!(
+            summary,
+            "{} stars, {} forks, {} open issues and pull requests. License: {}. Default branch: {}. Last pushed {}{}",
+            info.stargazers_count,
+            info.forks_count,
+            info.open_issues_count,
+            info.license
+                .and_then(|license| license.spdx_id)
+                .unwrap_or_else(|| "none".to_owned()),
+            info.default_branch,
+            info.pushed_at,
+            if info.archived { ". Archived" } else { "" }
+        );
+        if let Some(homepage) = info.homepage.filter(|homepage| !homepage.is_empty()) {
+            let _ = writeln!(summary, "Homepage: {}", homepage);
+        }
+        if !info.topics.is_empty() {
+            let _ = writeln!(summary, "Topics: {}", info.topics.join(", "));
+        }
+        let mut parts = vec![summary];
+        // repos without releases 404 here, which isn't worth failing over
+        if let Ok(release) = self
+            .json::<Release>(&format!("{}/releases/latest", base))
+            .await
+        {
+            parts.push(format!(
+                "## Latest release: {}{}\n\n{}",
+                release.name.unwrap_or(release.tag_name),
+                release
+                    .published_at
+                    .map(|date| format!(" ({})", date))
+                    .unwrap_or_default(),
+                truncate(release.body.as_deref().unwrap_or(""), MAX_GITHUB_CHARS / 4)
+            ));
+        }
+        let readme = self
+            .send(self.get_as(
+                &format!("{}/readme", base),
+                "application/vnd.github.raw+json",
+            ))
+            .await;
+        if let Ok(readme) = readme {
+            parts.push(format!(
+                "## README\n\n{}",
+                truncate(&readme.text().await?, MAX_GITHUB_CHARS / 2)
+            ));
+        }
+        Ok(parts)
+    }
+
+    async fn fetch_discussion(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        if self.token.is_none() {
+            return Err(anyhow!(
+                "GitHub discussions can only be fetched with a token, set --github-token"
+            ));
+        }
+        let response: GraphQlResponse<DiscussionData> = self
+            .send(
+                self.authorize(
+                    self.http.post(format!("{}/graphql", API)),
+                    "application/vnd.github+json",
+                )
+                .json(&json!({
+                    "query": DISCUSSION_QUERY,
+                    "variables": {"owner": owner, "repo": repo, "number": number},
+                })),
+            )
+            .await?
+            .json()
+            .await?;
+        if let Some(error) = response.errors.first() {
+            return Err(anyhow!("GitHub GraphQL query failed: {}", error.message));
+        }
+        let discussion = response
+            .data
+            .and_then(|data| data.repository)
+            .and_then(|repo| repo.discussion)
+            .ok_or_else(|| anyhow!("GitHub returned no discussion with this number"))?;
+        Ok(render_discussion(
+            &format!("{}/{}", owner, repo),
+            number,
+            discussion,
+        ))
+    }
+}
+
+fn login(user: Option<&User>) -> &str {
+    user.map_or("ghost", |user| user.login.as_str())
+}
+
+fn pull_details(pull: &Pull) -> String {
+    let state = if pull.merged {
+        format!("Merged {}", pull.merged_at.as_deref().unwrap_or(""))
+    } else if pull.draft {
+        "Draft".to_owned()
+    } else {
+        "Not merged".to_owned()
+    };
+    format!(
+        "{}. Merges {} into {}, +{} -{} in {} files",
+        state, pull.head.label, pull.base.label, pull.additions, pull.deletions, pull.changed_files
+    )
+}
+
+fn linked_pulls(timeline: Vec<TimelineEvent>) -> String {
+    let pulls: Vec<String> = timeline
+        .into_iter()
+        .filter(|event| event.event.as_deref() == Some("cross-referenced"))
+        .filter_map(|event| event.source?.issue)
+        .filter(|issue| issue.pull_request.is_some())
+        .map(|pull| format!("{} ({}, {})", pull.html_url, pull.title, pull.state))
+        .collect();
+    if pulls.is_empty() {
+        String::new()
+    } else {
+        format!("Linked pull requests: {}", pulls.join("; "))
+    }
+}
+
+/// Renders the issue or pull request followed by its comments, the most reacted to first.
+/// Comments keep their number in the thread, so their order can still be followed
+fn render_issue(
+    repo: &str,
+    issue: Issue,
+    details: String,
+    comments: Vec<Comment>,
+    is_pull: bool,
+) -> Vec<String> {
+    let mut header = format!(
+        "# {} (#{})\n\n{} in {}, {}{}, opened by {} on {}, {} reactions",
+        issue.title,
+        issue.number,
+        if is_pull { "Pull request" } else { "Issue" },
+        repo,
+        issue.state,
+        issue
+            .state_reason
+            .map(|reason| format!(" ({})", reason))
+            .unwrap_or_default(),
+        issue.user.login,
+        issue.created_at,
+        issue.reactions.total_count
+    );
+    if !issue.labels.is_empty() {
+        let labels: Vec<_> = issue
+            .labels
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect();
+        let _ = write!(header, "\nLabels: {}", labels.join(", "));
+    }
+    if !details.is_empty() {
+        let _ = write!(header, "\n{}", details);
+    }
+    let body = truncate(
+        issue.body.as_deref().unwrap_or("(no description)"),
+        MAX_GITHUB_CHARS / 2,
+    );
+    let mut thread = vec![format!("{}\n\n{}", header, body)];
+    let mut comments: Vec<(usize, Comment)> = comments
+        .into_iter()
+        .enumerate()
+        .map(|(index, comment)| (index + 1, comment))
+        .collect();
+    // a stable sort, so equally popular comments stay in order
+    comments.sort_by_key(|(_, comment)| std::cmp::Reverse(comment.reactions.total_count));
+    push_within_budget(
+        &mut thread,
+        comments.into_iter().map(|(number, comment)| {
+            format!(
+                "## Comment #{} from {} ({}, {} reactions)\n\n{}",
+                number,
+                comment.user.login,
+                comment.created_at,
+                comment.reactions.total_count,
+                comment.body.unwrap_or_default()
+            )
+        }),
+    );
+    thread
+}
+
+fn render_discussion(repo: &str, number: u64, discussion: Discussion) -> Vec<String> {
+    let answer = discussion.answer.map(|answer| answer.id);
+    let header = format!(
+        "# {} (discussion #{})\n\nDiscussion in {}{}, started by {} on {}, {} upvotes{}",
+        discussion.title,
+        number,
+        repo,
+        discussion
+            .category
+            .map(|category| format!(" ({})", category.name))
+            .unwrap_or_default(),
+        login(discussion.author.as_ref()),
+        discussion.created_at,
+        discussion.upvote_count,
+        if answer.is_some() { ", answered" } else { "" }
+    );
+    let mut thread = vec![format!(
+        "{}\n\n{}",
+        header,
+        truncate(&discussion.body, MAX_GITHUB_CHARS / 2)
+    )];
+    let mut comments: Vec<(usize, DiscussionComment)> = discussion
+        .comments
+        .nodes
+        .into_iter()
+        .enumerate()
+        .map(|(index, comment)| (index + 1, comment))
+        .collect();
+    // the answer first, then by upvotes
+    comments.sort_by_key(|(_, comment)| {
+        (
+            answer.as_ref() != Some(&comment.id),
+            std::cmp::Reverse(comment.upvote_count),
+        )
+    });
+    push_within_budget(
+        &mut thread,
+        comments.into_iter().map(|(index, comment)| {
+            format!(
+                "## Comment #{} from {}{} ({}, {} upvotes)\n\n{}",
+                index,
+                login(comment.author.as_ref()),
+                if answer.as_ref() == Some(&comment.id) {
+                    " (answer)"
+                } else {
+                    ""
+                },
+                comment.created_at,
+                comment.upvote_count,
+                comment.body
+            )
+        }),
+    );
+    thread
+}
+
+/// Renders a file with line numbers, so they can be referred to, limited to `lines` if given
+fn render_blob(repo: &str, path: &str, source: &str, lines: Option<(usize, usize)>) -> String {
+    let total = source.lines().count();
+    let (start, end) = lines.unwrap_or((1, total));
+    let end = end.min(total);
+    let mut header = format!("# {} ({})\n\n", path, repo);
+    if start > total {
+        let _ = write!(header, "The file only has {} lines", total);
+        return header;
+    }
+    let _ = writeln!(header, "Lines {}-{} of {}", start, end, total);
+    let language = path.rsplit_once('.').map_or("", |(_, extension)| extension);
+    let width = end.to_string().len();
+    let mut code = String::new();
+    for (number, line) in source.lines().enumerate().take(end).skip(start - 1) {
+        let _ = writeln!(code, "{:>width$} | {}", number + 1, line);
+    }
+    format!(
+        "{}\n```{}\n{}```",
+        header,
+        language,
+        truncate(&code, MAX_GITHUB_CHARS)
+    )
+}
+
+fn render_code_search(results: CodeSearch) -> Vec<String> {
+    let mut parts = vec![format!(
+        "{} files match, showing {}",
+        results.total_count,
+        results.items.len()
+    )];
+    for item in results.items {
+        let mut part = format!(
+            "## {}: {}\n\n{}\n",
+            item.repository.full_name, item.path, item.html_url
+        );
+        for text_match in item.text_matches {
+            let _ = write!(part, "\n```\n{}\n```\n", text_match.fragment.trim_end());
+        }
+        parts.push(part);
+    }
+    parts
+}
+
+/// Adds parts to the thread until it would exceed the budget, then notes how many were left out
+fn push_within_budget(thread: &mut Vec<String>, parts: impl ExactSizeIterator<Item = String>) {
+    let total = parts.len();
+    let mut used: usize = thread.iter().map(|part| part.chars().count()).sum();
+    let mut added = 0;
+    for part in parts {
+        let length = part.chars().count();
+        if used + length > MAX_GITHUB_CHARS {
+            break;
+        }
+        used += length;
+        added += 1;
+        thread.push(part);
+    }
+    if added < total {
+        thread.push(format!(
+            "({} less reacted to comments left out)",
+            total - added
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        let parse = |url: &str| GitHubPage::parse(&Url::parse(url).unwrap());
+        assert_eq!(
+            parse("https://github.com/tokio-rs/tokio/issues/1234"),
+            Some(GitHubPage::Issue {
+                owner: "tokio-rs".into(),
+                repo: "tokio".into(),
+                number: 1234
+            })
+        );
+        assert!(matches!(
+            parse("https://github.com/tokio-rs/tokio/pull/99/files"),
+            Some(GitHubPage::Pull { number: 99, .. })
+        ));
+        assert!(matches!(
+            parse("https://github.com/tokio-rs/tokio/"),
+            Some(GitHubPage::Repo { .. })
+        ));
+        assert_eq!(
+            parse("https://github.com/tokio-rs/tokio/blob/master/tokio/src/sync/mutex.rs#L10-L40"),
+            Some(GitHubPage::Blob {
+                owner: "tokio-rs".into(),
+                repo: "tokio".into(),
+                reference: "master".into(),
+                path: "tokio/src/sync/mutex.rs".into(),
+                lines: Some((10, 40)),
+            })
+        );
+        assert!(matches!(
+            parse("https://raw.githubusercontent.com/tokio-rs/tokio/tokio-1.47.1/Cargo.toml"),
+            Some(GitHubPage::Blob { lines: None, ref path, .. }) if path == "Cargo.toml"
+        ));
+        assert_eq!(line_range("L7"), Some((7, 7)));
+        assert_eq!(line_range("L40-L10"), None);
+        assert_eq!(parse("https://github.com/tokio-rs/tokio/blob/master"), None);
+        assert_eq!(parse("https://github.com/tokio-rs"), None);
+        assert_eq!(parse("https://github.com/tokio-rs/tokio/settings"), None);
+        assert_eq!(parse("https://github.com/features/actions"), None);
+        assert_eq!(parse("https://github.com/orgs/tokio-rs/repositories"), None);
+        assert_eq!(parse("https://github.com/settings/tokens"), None);
+        assert_eq!(parse("https://github.com/Marketplace/actions"), None);
+    }
+
+    #[test]
+    fn test_render_issue() {
+        let issue: Issue =
+            serde_json::from_str(&fs::read_to_string("testdata/github-issue.json").unwrap())
+                .unwrap();
+        let comments: Vec<Comment> =
+            serde_json::from_str(&fs::read_to_string("testdata/github-comments.json").unwrap())
+                .unwrap();
+        let timeline: Vec<TimelineEvent> =
+            serde_json::from_str(&fs::read_to_string("testdata/github-timeline.json").unwrap())
+                .unwrap();
+        let thread = render_issue(
+            "tokio-rs/tokio",
+            issue,
+            linked_pulls(timeline),
+            comments,
+            false,
+        );
+        assert!(thread[0].starts_with("# Mutex::lock is not cancel safe (#5512)"));
+        assert!(thread[0].contains("Issue in tokio-rs/tokio, closed (completed)"));
+        assert!(thread[0].contains("Labels: C-bug, M-sync"));
+        assert!(thread[0].contains(
+            "Linked pull requests: https://github.com/tokio-rs/tokio/pull/5520 (sync: document cancel safety, closed)"
+        ));
+        // the most reacted to comment comes first, keeping its place in the thread
+        assert!(thread[1].starts_with("## Comment #2 from Darksonn"));
+        assert!(thread[2].starts_with("## Comment #1 from someone"));
+    }
+
+    #[test]
+    fn test_render_blob() {
+        let source: String = (1..=12).map(|n| format!("line {}\n", n)).collect();
+        let text = render_blob("o/r@main", "src/lib.rs", &source, Some((9, 20)));
+        assert!(text.contains("Lines 9-12 of 12"));
+        assert!(text.contains("```rs\n 9 | line 9\n10 | line 10\n"));
+        assert!(!text.contains("line 8"));
+        assert!(render_blob("o/r@main", "a.rs", &source, Some((13, 14))).contains("only has 12"));
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::Context;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use url::Url;
+
+use crate::{
+    http::HttpClient,
+    passage::{html_to_markdown, strip_tags, truncate},
+    search::days_from_civil,
+    thread::{Comment, MAX_THREAD_CHARS, Thread},
+};
+
+const ALGOLIA_API: &str = "https://hn.algolia.com/api/v1";
+
+/// Replies nested deeper than this are left out, as they rarely stay on topic
+const MAX_DEPTH: usize = 6;
+
+/// How much of a comment search result is shown
+const SNIPPET_CHARS: usize = 300;
+
+#[derive(Deserialize)]
+struct Item {
+    id: u64,
+    created_at: String,
+    author: Option<String>,
+    title: Option<String>,
+    url: Option<String>,
+    /// HTML, null for deleted comments and link stories
+    text: Option<String>,
+    points: Option<i64>,
+    #[serde(default)]
+    children: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SearchResponse {
+    hits: Vec<Hit>,
+    nb_hits: u64,
+}
+
+#[derive(Deserialize)]
+struct Hit {
+    #[serde(rename = "objectID")]
+    object_id: String,
+    created_at: String,
+    author: Option<String>,
+    title: Option<String>,
+    url: Option<String>,
+    points: Option<i64>,
+    num_comments: Option<u64>,
+    /// set on comments, with the title of the story they're on
+    story_title: Option<String>,
+    comment_text: Option<String>,
+}
+
+/// The kinds of Hacker News items `search_hn` looks for
+#[derive(Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HnKind {
+    Story,
+    Comment,
+    AskHn,
+    ShowHn,
+}
+
+impl HnKind {
+    /// The Algolia tag of the kind
+    fn tag(&self) -> &'static str {
+        match self {
+            HnKind::Story => "story",
+            HnKind::Comment => "comment",
+            HnKind::AskHn => "ask_hn",
+            HnKind::ShowHn => "show_hn",
+        }
+    }
+}
+
+/// A `search_hn` query. Dates are seconds since the Unix epoch
+pub struct HnSearch<'a> {
+    pub query: &'a str,
+    pub kind: HnKind,
+    pub after: Option<u64>,
+    pub before: Option<u64>,
+    /// newest first rather than most relevant first
+    pub by_date: bool,
+    pub num: u8,
+}
+
+/// A YYYY-MM-DD date as seconds since the Unix epoch
+pub fn parse_date(date: &str) -> Option<u64> {
+    let mut parts = date.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    // years past four digits would overflow the day count
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let days = days_from_civil(year, month, day);
+    u64::try_from(days)
+        .ok()
+        .and_then(|days| days.checked_mul(24 * 60 * 60))
+}
+
+/// The item id of a `news.ycombinator.com/item?id=` URL
+pub fn item_id(url: &Url) -> Option<u64> {
+    if url.path() != "/item" {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "id")
+        .and_then(|(_, id)| id.parse().ok())
+}
+
+/// Fetches a story or comment with its replies through the Algolia items API, which returns
+/// the whole tree in one request, unlike the official API's item per request
+pub async fn fetch_item(http: &HttpClient, id: u64) -> Result<Vec<String>, anyhow::Error> {
+    let item: Item = http
+        .send(http.get(format!("{}/items/{}", ALGOLIA_API, id)))
+        .await
+        .context("fetching hacker news item")?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(render(item))
+}
+
+/// Searches stories or comments through the Algolia search API
+pub async fn search(
+    http: &HttpClient,
+    search: &HnSearch<'_>,
+) -> Result<Vec<String>, anyhow::Error> {
+    let endpoint = if search.by_date {
+        "search_by_date"
+    } else {
+        "search"
+    };
+    let mut filters = Vec::new();
+    if let Some(after) = search.after {
+        filters.push(format!("created_at_i>={}", after));
+    }
+    if let Some(before) = search.before {
+        filters.push(format!("created_at_i<{}", before));
+    }
+    let mut query = vec![
+        ("query", search.query.to_owned()),
+        ("tags", search.kind.tag().to_owned()),
+        ("hitsPerPage", search.num.to_string()),
+    ];
+    if !filters.is_empty() {
+        query.push(("numericFilters", filters.join(",")));
+    }
+    let results: SearchResponse = http
+        .send(
+            http.get(format!("{}/{}", ALGOLIA_API, endpoint))
+                .query(&query),
+        )
+        .await
+        .context("searching hacker news")?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(render_search(results))
+}
+
+/// Renders the item, then its replies as a `Thread`, numbered in the order they're shown with
+/// the item itself as 0
+fn render(item: Item) -> Vec<String> {
+    let mut header = match &item.title {
+        Some(title) => format!("# {}\n\n", title),
+        None => String::new(),
+    };
+    header.push_str(&format!(
+        "Posted {} by {}",
+        date(&item.created_at),
+        item.author.as_deref().unwrap_or("[deleted]")
+    ));
+    if let Some(points) = item.points {
+        header.push_str(&format!(", {} points", points));
+    }
+    header.push_str(&format!(", {} comments", count(&item.children)));
+    if let Some(url) = &item.url {
+        header.push_str(&format!("\n\n{}", url));
+    }
+    if let Some(text) = &item.text {
+        header.push_str(&format!("\n\n{}", html_to_markdown(text)));
+    }
+    header.push_str(&format!(
+        "\n\nhttps://news.ycombinator.com/item?id={}",
+        item.id
+    ));
+    let mut thread = Thread::new(header, MAX_THREAD_CHARS);
+    push_children(&mut thread, &item.children, 0, &mut 1, 1);
+    thread.into_parts()
+}
+
+/// Adds replies depth first, so each is followed by the replies to it
+fn push_children(
+    thread: &mut Thread,
+    children: &[Item],
+    parent: usize,
+    next_id: &mut usize,
+    depth: usize,
+) {
+    if depth > MAX_DEPTH {
+        thread.leave_out(count(children));
+        return;
+    }
+    for child in children {
+        // deleted comments have no text; their replies answer the comment above them
+        let Some(text) = &child.text else {
+            push_children(thread, &child.children, parent, next_id, depth + 1);
+            continue;
+        };
+        let id = *next_id;
+        let added = thread.push(Comment {
+            id,
+            author: child.author.as_deref().unwrap_or("[deleted]").to_owned(),
+            parent: Some(parent),
+            details: date(&child.created_at),
+            body: html_to_markdown(text),
+        });
+        if added {
+            *next_id += 1;
+        }
+        push_children(thread, &child.children, id, next_id, depth + 1);
+    }
+}
+
+/// The number of comments with text in a tree of replies
+fn count(children: &[Item]) -> usize {
+    children
+        .iter()
+        .map(|child| usize::from(child.text.is_some()) + count(&child.children))
+        .sum()
+}
+
+/// "2024-03-01T12:34:56.000Z" as "2024-03-01 12:34"
+fn date(created_at: &str) -> String {
+    created_at.get(..16).unwrap_or(created_at).replace('T', " ")
+}
+
+fn render_search(results: SearchResponse) -> Vec<String> {
+    let mut parts = vec![format!(
+        "{} items match, showing {}",
+        results.nb_hits,
+        results.hits.len()
+    )];
+    for hit in results.hits {
+        let author = hit.author.as_deref().unwrap_or("[deleted]");
+        let link = format!("https://news.ycombinator.com/item?id={}", hit.object_id);
+        let part = match (&hit.title, &hit.comment_text) {
+            (Some(title), _) => {
+                let mut part = format!(
+                    "## {}\n\n{} points, {} comments, posted {} by {}\n{}",
+                    title,
+                    hit.points.unwrap_or(0),
+                    hit.num_comments.unwrap_or(0),
+                    date(&hit.created_at),
+                    author,
+                    link
+                );
+                if let Some(url) = &hit.url {
+                    part.push_str(&format!("\n{}", url));
+                }
+                part
+            }
+            (None, comment) => format!(
+                "## Comment by {} on {}\n\nposted {}\n{}\n\n{}",
+                author,
+                hit.story_title.as_deref().unwrap_or("a story"),
+                date(&hit.created_at),
+                link,
+                truncate(&strip_tags(comment.as_deref().unwrap_or("")), SNIPPET_CHARS)
+            ),
+        };
+        parts.push(part);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_render_item() {
+        let item: Item =
+            serde_json::from_str(&fs::read_to_string("testdata/hn-item.json").unwrap()).unwrap();
+        let thread = render(item);
+        assert_eq!(thread.len(), 5);
+        assert!(thread[0].starts_with("# Is SQLite production ready?\n\nPosted 2024-03-01 12:34 by pg_fan, 321 points, 4 comments"));
+        assert!(thread[0].contains("We're weighing SQLite"));
+        assert!(thread[0].ends_with("https://news.ycombinator.com/item?id=39000000"));
+        assert!(
+            thread[1].starts_with(
+                "<h1>Comment: #1 from dba In response to: 0</h1><p>2024-03-01 13:00</p>"
+            )
+        );
+        assert!(thread[1].contains("WAL mode"));
+        assert!(thread[2].starts_with("<h1>Comment: #2 from skeptic In response to: 1</h1>"));
+        // the reply to a deleted comment answers the comment above it
+        assert!(thread[3].starts_with("<h1>Comment: #3 from lurker In response to: 0</h1>"));
+        assert!(thread[4].starts_with("<h1>Comment: #4 from ops In response to: 0</h1>"));
+
+        let url = |url: &str| item_id(&Url::parse(url).unwrap());
+        assert_eq!(
+            url("https://news.ycombinator.com/item?id=39000000"),
+            Some(39000000)
+        );
+        assert_eq!(url("https://news.ycombinator.com/news?p=2"), None);
+        assert_eq!(parse_date("2024-03-01"), Some(1709251200));
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("1969-12-31"), None);
+        assert_eq!(parse_date("99999999999999999-01-01"), None);
+    }
+
+    #[test]
+    fn test_render_search() {
+        let results: SearchResponse =
+            serde_json::from_str(&fs::read_to_string("testdata/hn-search.json").unwrap()).unwrap();
+        let parts = render_search(results);
+        assert_eq!(parts[0], "1520 items match, showing 2");
+        assert_eq!(
+            parts[1],
+            "## Is SQLite production ready?\n\n321 points, 4 comments, posted 2024-03-01 12:34 by pg_fan\nhttps://news.ycombinator.com/item?id=39000000"
+        );
+        assert!(parts[2].starts_with("## Comment by dba on Is SQLite production ready?"));
+        assert!(parts[2].ends_with("Yes, in WAL mode & with backups."));
+    }
+}
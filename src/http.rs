@@ -0,0 +1,271 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use reqwest::{
+    RequestBuilder, Response, StatusCode,
+    header::{HeaderMap, RETRY_AFTER, USER_AGENT},
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Hosts tracked before idle ones are forgotten
+const MAX_TRACKED_HOSTS: usize = 256;
+
+/// Controls how requests that fail with a transient error (429, 5xx, timeouts and connection
+/// failures) are retried
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// the delay before the first retry, doubled on each following attempt
+    pub base_delay: Duration,
+    /// the longest we'll wait between attempts, including when told to by a Retry-After header
+    pub max_delay: Duration,
+}
+
+/// The HTTP client shared by every source, so connection pools, TLS sessions and DNS lookups are
+/// reused across tool calls
+pub struct HttpClient {
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl HttpClient {
+    pub fn new(
+        user_agent: &str,
+        connect_timeout: Duration,
+        read_timeout: Duration,
+        retry: RetryPolicy,
+    ) -> Result<Self, anyhow::Error> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, user_agent.parse()?);
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(connect_timeout)
+            .read_timeout(read_timeout)
+            .build()?;
+        Ok(Self { client, retry })
+    }
+
+    pub fn get(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: impl reqwest::IntoUrl) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    /// Sends the request, retrying with exponential backoff and jitter on transient failures.
+    /// A Retry-After header on the response takes precedence over the computed backoff, unless
+    /// it asks us to wait longer than the policy allows, in which case the response is returned
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response, reqwest::Error> {
+        self.send_retrying(request, true).await
+    }
+
+    /// Like `send`, but returns 429 responses at once. For APIs like Google's that answer 429
+    /// when a daily quota is used up, where retrying can't succeed and spends more of the quota
+    pub async fn send_quota_limited(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<Response, reqwest::Error> {
+        self.send_retrying(request, false).await
+    }
+
+    async fn send_retrying(
+        &self,
+        request: RequestBuilder,
+        retry_too_many_requests: bool,
+    ) -> Result<Response, reqwest::Error> {
+        let mut attempt = 0;
+        loop {
+            // requests with streaming bodies can't be cloned, so they only get one shot
+            let Some(this_attempt) = request.try_clone() else {
+                return request.send().await;
+            };
+            let retries_left = attempt < self.retry.max_retries;
+            let delay = match this_attempt.send().await {
+                Ok(res)
+                    if retries_left
+                        && is_retryable(res.status())
+                        && (retry_too_many_requests
+                            || res.status() != StatusCode::TOO_MANY_REQUESTS) =>
+                {
+                    match retry_after(res.headers()) {
+                        Some(delay) if delay > self.retry.max_delay => return Ok(res),
+                        Some(delay) => delay,
+                        None => self.backoff(attempt),
+                    }
+                }
+                Err(err) if retries_left && (err.is_timeout() || err.is_connect()) => {
+                    self.backoff(attempt)
+                }
+                result => return result,
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Exponential backoff with "equal jitter": half the delay is fixed, the other half random,
+    /// so concurrent callers don't retry in lockstep
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .retry
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.retry.max_delay);
+        let half = exponential.as_millis() as u64 / 2;
+        Duration::from_millis(half + fastrand::u64(0..=half))
+    }
+}
+
+/// Limits how many requests are in flight to each host at once, across all tool calls, so batch
+/// fetches don't hammer a single site
+pub struct HostLimiter {
+    per_host: usize,
+    hosts: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostLimiter {
+    pub fn new(per_host: usize) -> Self {
+        Self {
+            per_host: per_host.max(1),
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Waits for a free slot for `host`, which is held until the permit is dropped
+    pub async fn acquire(&self, host: &str) -> OwnedSemaphorePermit {
+        let semaphore = {
+            let mut hosts = self.hosts.lock().unwrap();
+            if hosts.len() >= MAX_TRACKED_HOSTS {
+                hosts.retain(|_, semaphore| semaphore.available_permits() < self.per_host);
+            }
+            hosts
+                .entry(host.to_owned())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.per_host)))
+                .clone()
+        };
+        // the semaphore is never closed
+        semaphore.acquire_owned().await.unwrap()
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parses a Retry-After header, which is either a number of seconds or an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, "120".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        // dates in the past mean "retry now"
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+
+    #[tokio::test]
+    async fn test_host_limiter() {
+        let limiter = HostLimiter::new(2);
+        let _first = limiter.acquire("a.com").await;
+        let second = limiter.acquire("a.com").await;
+        let wait = Duration::from_millis(20);
+        assert!(
+            tokio::time::timeout(wait, limiter.acquire("a.com"))
+                .await
+                .is_err()
+        );
+        // other hosts aren't affected
+        let _other = limiter.acquire("b.com").await;
+        drop(second);
+        assert!(
+            tokio::time::timeout(wait, limiter.acquire("a.com"))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quota_429_not_retried() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let counter = hits.clone();
+        let router = axum::Router::new().route(
+            "/search",
+            axum::routing::get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        r#"{"error": {"code": 429, "status": "RESOURCE_EXHAUSTED"}}"#,
+                    )
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/search", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let client = HttpClient::new(
+            "test",
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(10),
+                max_delay: Duration::from_millis(20),
+            },
+        )
+        .unwrap();
+
+        let res = client.send_quota_limited(client.get(&url)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+        // other callers still retry rate limits
+        client.send(client.get(&url)).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_backoff_bounds() {
+        let client = HttpClient::new(
+            "test",
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            RetryPolicy {
+                max_retries: 3,
+                base_delay: Duration::from_millis(100),
+                max_delay: Duration::from_millis(1000),
+            },
+        )
+        .unwrap();
+        for _ in 0..20 {
+            let first = client.backoff(0);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let capped = client.backoff(10);
+            assert!(capped >= Duration::from_millis(500) && capped <= Duration::from_millis(1000));
+        }
+    }
+}
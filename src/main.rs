@@ -1,19 +1,34 @@
-use std::{collections::HashMap, fs::File, io::Read, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    net::SocketAddr,
+    path::PathBuf,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use anyhow::{Context, anyhow};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser, ValueEnum};
 use regex::Regex;
-use reqwest::header::{HeaderMap, USER_AGENT};
+use reqwest::header::USER_AGENT;
 use rmcp::{
-    ErrorData, ServiceExt,
-    handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{CallToolResult, Content, ServerCapabilities, ServerInfo},
+    ErrorData, RoleServer, ServiceExt,
+    handler::server::{router::tool::ToolRouter, tool::ToolCallContext, wrapper::Parameters},
+    model::{
+        CallToolRequestParam, CallToolResult, Content, ListToolsResult, PaginatedRequestParam,
+        ServerCapabilities, ServerInfo,
+    },
     schemars::JsonSchema,
-    tool, tool_handler, tool_router,
-    transport::stdio,
+    service::RequestContext,
+    tool, tool_router,
+    transport::{
+        stdio,
+        streamable_http_server::{StreamableHttpService, session::local::LocalSessionManager},
+    },
 };
 use roux::{
-    MaybeReplies,
+    Comments, MaybeReplies, Submissions,
     comment::CommentData,
     response::{BasicThing, Listing},
 };
@@ -22,48 +37,377 @@ use serde_json::Value;
 use std::sync::LazyLock;
 use url::Url;
 
+use crate::{
+    auth::{Authenticator, TokenPolicy},
+    cache::{CacheSource, CacheTtls, ResponseCache, canonical_url, params_key},
+    docsets::Docsets,
+    http::{HostLimiter, HttpClient, RetryPolicy},
+    man::ManPages,
+    python_docs::PythonDocs,
+    quota::DailyQuota,
+    results::{ResultFormat, SearchResults},
+    rust_docs::RustDocs,
+    search::{BackendKind, SearchBackend, SearchChain},
+    thread::{Comment, MAX_THREAD_CHARS, Thread},
+};
+
+mod auth;
+mod cache;
+mod config_file;
+mod crates;
+mod discourse;
+mod docsets;
+mod github;
+mod hacker_news;
+mod http;
+mod man;
+mod packages;
+mod passage;
+mod python_docs;
+mod quota;
+mod results;
+mod rust_docs;
+mod search;
+mod thread;
+
 static DOMXREF_RE: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r#"\{\{domxref\("(?P<arg>[^"]+)"\)\}\}"#).unwrap());
 static TEMPLATE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{[^}]+\}\}").unwrap());
+static DATE_RESTRICT_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^[dwmy]\d+$").unwrap());
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Transport {
+    /// serve a single client over stdin and stdout
+    Stdio,
+    /// serve any number of clients over MCP streamable HTTP (with SSE) on `--listen`
+    Http,
+}
 
 #[derive(Parser)]
 struct Config {
+    #[arg(long = "config", env = "CONFIG_FILE")]
+    /// A TOML file with a section per source, e.g. `[google]`, `[reddit]` or `[cache]`, holding
+    /// settings that aren't given as flags or env vars. Any key may be suffixed with `_file` to
+    /// read its value from that file instead
+    config_file: Option<PathBuf>,
+    #[arg(long, env, value_enum, default_value_t = Transport::Stdio)]
+    /// How clients connect to this server
+    transport: Transport,
+    #[arg(long, env, default_value_t = 0)]
+    /// The number of threads tool calls run on. 0 starts one per CPU core, so parallel tool
+    /// calls (and http clients) don't wait on each other; 1 runs everything on a single thread
+    worker_threads: usize,
+    #[arg(long, env, default_value = "127.0.0.1:8080")]
+    /// The address to listen on when using the http transport. The MCP endpoint is served at
+    /// `/mcp`, and a health check at `/health`
+    listen: SocketAddr,
+    #[arg(long, env, value_delimiter = ',')]
+    /// Comma separated bearer tokens accepted by the http transport, each allowed to call every
+    /// tool without limits. Prefer the environment variable, as arguments show up in `ps`
+    auth_tokens: Vec<String>,
+    #[arg(long, env)]
+    /// A JSON file listing bearer tokens accepted by the http transport, as objects with a
+    /// `token` and optionally a `name`, a `tools` allow-list and a `requests_per_minute` limit
+    auth_tokens_file: Option<PathBuf>,
+    #[arg(long, env)]
+    /// Serves the http transport without auth tokens, so anyone who can reach it can use the
+    /// server. Without it, the http transport refuses to start when no tokens are configured
+    allow_unauthenticated: bool,
+    #[arg(long, env, value_enum, value_delimiter = ',', default_value = "google")]
+    /// The search engines `query_google_search` uses, as a comma separated list. Each is only
+    /// tried when the ones before it fail, e.g. "google,searxng" falls back to SearXNG once the
+    /// Google quota is used up
+    search_backends: Vec<BackendKind>,
+    #[arg(long, env)]
+    /// The search engine ID, generated when a new custom search is created in Google. Required
+    /// by the google search backend
+    google_search_engine_id: Option<String>,
+    #[arg(long, env)]
+    /// An API key in Google APIs that has access to the Google Custom Search. Required by the
+    /// google search backend
+    google_search_api_key: Option<String>,
+    #[arg(long, env)]
+    /// A file containing the Google API key, e.g. a mounted Docker or Kubernetes secret
+    google_search_api_key_file: Option<PathBuf>,
+    #[arg(long, env, default_value_t = 100)]
+    /// The number of searches per day the Custom Search API allows (100 on the free tier), used
+    /// to warn in the logs and search results when the quota is nearly used up. Set to 0 to
+    /// disable
+    google_daily_quota: u32,
+    #[arg(long, env)]
+    /// The base URL of a SearXNG instance with the json format enabled, e.g.
+    /// http://localhost:8888. Required by the searxng search backend
+    searxng_url: Option<String>,
+    #[arg(long, env)]
+    /// A Brave Search API subscription token. Required by the brave search backend
+    brave_api_key: Option<String>,
     #[arg(long, env)]
-    /// The search engine ID, generated when a new custom search is created in Google
-    google_search_engine_id: String,
+    /// A file containing the Brave Search API token
+    brave_api_key_file: Option<PathBuf>,
     #[arg(long, env)]
-    /// An API key in Google APIs that has access to the Google Custom Search
-    google_search_api_key: String,
+    /// A Bing Web Search subscription key. Required by the bing search backend
+    bing_api_key: Option<String>,
+    #[arg(long, env)]
+    /// A file containing the Bing Web Search key
+    bing_api_key_file: Option<PathBuf>,
+    #[arg(
+        long,
+        env,
+        default_value = "https://api.bing.microsoft.com/v7.0/search"
+    )]
+    /// The Bing Web Search endpoint
+    bing_endpoint: String,
+    #[arg(long, env)]
+    /// A GitHub token, which raises the API rate limit from 60 to 5000 requests an hour and is
+    /// required to fetch discussions. Needs no scopes for public repositories
+    github_token: Option<String>,
+    #[arg(long, env)]
+    /// A file containing the GitHub token
+    github_token_file: Option<PathBuf>,
     #[arg(long, env)]
     /// The prefix, e.g. the API host and version of the Stack Exchange API
     stack_overflow_api_prefix: String,
     #[arg(long, env)]
     stack_overflow_api_key: Option<String>,
     #[arg(long, env)]
+    /// A file containing the Stack Exchange API key
+    stack_overflow_api_key_file: Option<PathBuf>,
+    #[arg(long, env)]
     /// The path where the MDN content github project lives, up to the leading "files" directory
     mdn_base_path: String,
     #[arg(long, env)]
+    /// The path of the Python docs built with the Sphinx text builder, or of a directory of
+    /// such builds named by version (e.g. "3.12" and "3.13"). Enables docs.python.org pages and
+    /// the search_python_docs tool
+    python_docs_path: Option<PathBuf>,
+    #[arg(long, env)]
+    /// The share/doc/rust directory of the pinned toolchain, e.g.
+    /// ~/.rustup/toolchains/1.89.0-x86_64-unknown-linux-gnu/share/doc/rust, with the docs of
+    /// the rust-docs component and optionally the rustdoc JSON of rust-docs-json. Enables
+    /// doc.rust-lang.org pages and the lookup_rust_item tool
+    rust_docs_path: Option<PathBuf>,
+    #[arg(long, env)]
+    /// A directory of offline docsets in the DevDocs format, each a directory named by the
+    /// docset's slug (e.g. "cpp" or "python~3.12") holding its index.json and db.json. Enables
+    /// devdocs.io pages and the search_docsets tool
+    docsets_path: Option<PathBuf>,
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        default_value = "c=https://en.cppreference.com/w/c/,cpp=https://en.cppreference.com/w/cpp/"
+    )]
+    /// Comma separated slug=url pairs giving the sites docsets were scraped from, so their pages
+    /// are read from the local docset
+    docset_urls: Vec<String>,
+    #[arg(long, env, default_value = "/usr/share/man:/usr/local/share/man")]
+    /// The directories the man_page tool reads man pages from, as a colon separated list like
+    /// MANPATH. man7.org pages installed in them are also read from them
+    man_path: String,
+    #[arg(long, env)]
+    /// Lets the man_page tool run a command on the server's PATH with --help, for commands
+    /// that have no man page. Off by default, as it runs programs named by the client
+    man_run_help: bool,
+    #[arg(long, env)]
     /// The reddit client id for reddit APIs
     reddit_client_id: String,
     #[arg(long, env)]
     /// The reddit client secret for reddit APIs
     reddit_client_secret: String,
     #[arg(long, env)]
+    /// A file containing the reddit client secret
+    reddit_client_secret_file: Option<PathBuf>,
+    #[arg(long, env)]
     /// The reddit username (required for Reddit oauth scripts). May create burner account
     reddit_username: String,
     #[arg(long, env)]
     reddit_password: String,
+    #[arg(long, env)]
+    /// A file containing the reddit password
+    reddit_password_file: Option<PathBuf>,
+    #[arg(long, env)]
+    /// The User-Agent sent to upstreams. Defaults to the crate name and version, followed by
+    /// the contact info if set
+    user_agent: Option<String>,
+    #[arg(long, env)]
+    /// Contact info (an email or URL) for operators of upstream sites to reach whoever runs this
+    /// server. Included in the default User-Agents
+    contact: Option<String>,
+    #[arg(long, env)]
+    /// The User-Agent sent to Reddit, which requires the form
+    /// `<platform>:<app ID>:<version> (by /u/<username>)`. Defaults to one built from the crate
+    /// name, version and reddit username
+    reddit_user_agent: Option<String>,
+    #[arg(
+        long,
+        env,
+        value_delimiter = ',',
+        default_value = "users.rust-lang.org,internals.rust-lang.org"
+    )]
+    /// Comma separated hosts of Discourse forums whose topics are fetched through the Discourse
+    /// JSON API
+    discourse_hosts: Vec<String>,
     #[arg(short = 's', long)]
     /// When set, enable Scrapper, the playwright and readability.js based web scraper to fetch
     /// pages without a more specific handler. Set to the host and port of the running Scrapper
     /// server
     /// Warning: Servers may reject traffic or have a CAPTCHA
     scrapper_host: Option<String>,
+    #[arg(long, env)]
+    /// When set, search results that `fetch_web_page` can't retrieve with the current
+    /// configuration are left out, rather than marked as not fetchable
+    only_fetchable_results: bool,
+    #[arg(long, env, default_value_t = 12000)]
+    /// The most characters of page content the research tool returns in one call, shared among
+    /// the pages it reads
+    research_budget: usize,
+    #[arg(long, env)]
+    /// When set, cached search and fetch results are persisted in this directory and reloaded
+    /// on startup. Otherwise the cache only lives in memory
+    cache_dir: Option<PathBuf>,
+    #[arg(long, env, default_value_t = 1000)]
+    /// The maximum number of cached responses kept. Set to 0 to disable caching
+    cache_max_entries: usize,
+    #[arg(long, env, default_value_t = 6 * 60 * 60)]
+    /// How long, in seconds, search results are cached
+    cache_ttl_google: u64,
+    #[arg(long, env, default_value_t = 3 * 24 * 60 * 60)]
+    /// How long, in seconds, Stack Overflow questions and answers are cached
+    cache_ttl_stack_overflow: u64,
+    #[arg(long, env, default_value_t = 60 * 60)]
+    /// How long, in seconds, Reddit threads are cached
+    cache_ttl_reddit: u64,
+    #[arg(long, env, default_value_t = 60 * 60)]
+    /// How long, in seconds, Hacker News threads and searches are cached
+    cache_ttl_hacker_news: u64,
+    #[arg(long, env, default_value_t = 6 * 60 * 60)]
+    /// How long, in seconds, Discourse topics are cached
+    cache_ttl_discourse: u64,
+    #[arg(long, env, default_value_t = 6 * 60 * 60)]
+    /// How long, in seconds, crates.io metadata and docs.rs pages are cached
+    cache_ttl_crates: u64,
+    #[arg(long, env, default_value_t = 60 * 60)]
+    /// How long, in seconds, GitHub issues, pull requests, discussions and repos are cached
+    cache_ttl_github: u64,
+    #[arg(long, env, default_value_t = 60 * 60)]
+    /// How long, in seconds, npm, PyPI, Go and Maven package metadata is cached
+    cache_ttl_packages: u64,
+    #[arg(long, env, default_value_t = 24 * 60 * 60)]
+    /// How long, in seconds, pages fetched through Scrapper are cached
+    cache_ttl_scrapper: u64,
+    #[arg(long, env, default_value_t = 10)]
+    /// How long, in seconds, to wait for a connection to an upstream to be established
+    http_connect_timeout: u64,
+    #[arg(long, env, default_value_t = 30)]
+    /// How long, in seconds, to wait on a read from an upstream before giving up
+    http_read_timeout: u64,
+    #[arg(long, env, default_value_t = 3)]
+    /// How many times a request failing with 429, 5xx or a timeout is retried
+    http_max_retries: u32,
+    #[arg(long, env, default_value_t = 2)]
+    /// How many pages may be fetched from the same host at once, e.g. by fetch_web_pages
+    http_per_host_concurrency: usize,
+    #[arg(long, env, default_value_t = 30)]
+    /// The longest, in seconds, to wait between retries. If an upstream's Retry-After asks for
+    /// longer than this, the request fails instead
+    http_retry_max_delay: u64,
+}
+
+impl Config {
+    /// Parses flags and env vars, falling back to the config file and secret files for anything
+    /// not given. Flags take precedence over env vars, which take precedence over `*_file`
+    /// secrets, then the config file, then the built-in defaults
+    fn load() -> Result<Self, anyhow::Error> {
+        // a first pass, tolerating missing arguments, to find the files to read
+        let partial = Config::command().ignore_errors(true).get_matches();
+        let defaults = config_file::defaults(
+            partial
+                .try_get_one::<PathBuf>("config_file")
+                .ok()
+                .flatten()
+                .map(PathBuf::as_path),
+            &partial,
+        )?;
+        let mut command = Config::command();
+        for (id, value) in defaults {
+            command = command.mut_arg(id, |arg| arg.default_value(value).required(false));
+        }
+        Ok(Config::from_arg_matches_mut(&mut command.get_matches())?)
+    }
+
+    /// The configured search backends, in the order they're tried
+    fn search_chain(&self) -> Result<SearchChain, anyhow::Error> {
+        let required = |value: &Option<String>, flag: &str, backend: &str| {
+            value
+                .clone()
+                .ok_or_else(|| anyhow!("the {} search backend requires --{}", backend, flag))
+        };
+        let backends = self
+            .search_backends
+            .iter()
+            .map(|kind| {
+                Ok(match kind {
+                    BackendKind::Google => SearchBackend::Google {
+                        engine_id: required(
+                            &self.google_search_engine_id,
+                            "google-search-engine-id",
+                            "google",
+                        )?,
+                        api_key: required(
+                            &self.google_search_api_key,
+                            "google-search-api-key",
+                            "google",
+                        )?,
+                        quota: DailyQuota::new(self.google_daily_quota),
+                    },
+                    BackendKind::Searxng => SearchBackend::Searxng {
+                        url: required(&self.searxng_url, "searxng-url", "searxng")?,
+                    },
+                    BackendKind::Brave => SearchBackend::Brave {
+                        api_key: required(&self.brave_api_key, "brave-api-key", "brave")?,
+                    },
+                    BackendKind::Bing => SearchBackend::Bing {
+                        api_key: required(&self.bing_api_key, "bing-api-key", "bing")?,
+                        endpoint: self.bing_endpoint.clone(),
+                    },
+                })
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        Ok(SearchChain::new(backends))
+    }
+
+    fn user_agent(&self) -> String {
+        if let Some(user_agent) = self.user_agent.as_ref() {
+            return user_agent.clone();
+        }
+        let base = format!("{}/{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        match self.contact.as_ref() {
+            Some(contact) => format!("{} (+{})", base, contact),
+            None => base,
+        }
+    }
+
+    fn reddit_user_agent(&self) -> String {
+        if let Some(user_agent) = self.reddit_user_agent.as_ref() {
+            return user_agent.clone();
+        }
+        let base = format!(
+            "{}:{}:v{} (by /u/{})",
+            std::env::consts::OS,
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+            self.reddit_username
+        );
+        match self.contact.as_ref() {
+            Some(contact) => format!("{} {}", base, contact),
+            None => base,
+        }
+    }
 }
 
 #[derive(Deserialize, Default, JsonSchema)]
 #[serde(rename_all = "snake_case")]
-struct GoogleSearchParams {
+struct SearchParams {
     /// a list of words that _all_ must match _exactly_ as a space separated string
     /// generally this should be provided to ensure accurate and on-topic results
     exact_terms: Option<String>,
@@ -75,32 +419,439 @@ struct GoogleSearchParams {
     start: Option<u8>,
     /// the required query itself, the search term(s), as a string. E.g. "typescript enum to string method"
     query: String,
+    /// a list of words, of which _at least one_ must be in each result, as a space separated
+    /// string. Useful for synonyms, e.g. "tokio async-std smol"
+    or_terms: Option<String>,
+    /// restricts results to pages from the last so many days, weeks, months or years, written
+    /// as a letter and a number: "d7" is the last week, "m6" the last 6 months, "y1" the last
+    /// year. Use it for fast-moving frameworks, where old answers are often wrong
+    date_restrict: Option<String>,
+    /// a site (host, optionally with a path) to restrict results to, or to exclude from results
+    /// (see `site_search_filter`). E.g. "docs.rs" or "github.com/tokio-rs"
+    site_search: Option<String>,
+    /// whether `site_search` results are the only ones included (the default) or are excluded
+    site_search_filter: Option<SiteSearchFilter>,
+    /// the number of results to return, from 1 to 10. Defaults to 10
+    num: Option<u8>,
+    /// restricts results to documents in a language, as "lang_" and a language code, e.g.
+    /// "lang_en" or "lang_de"
+    lr: Option<String>,
+    /// boosts results from a country, as a two letter country code, e.g. "us" or "de"
+    gl: Option<String>,
+    /// "relevance" (the default) or "date" to put the newest results first
+    sort: Option<SortOrder>,
+    /// restricts results to files of an extension, e.g. "pdf" or "md"
+    file_type: Option<String>,
+    /// how to present the results: "json" (the default) for structured results, or "text" for a
+    /// shorter plain text list
+    format: Option<ResultFormat>,
+}
+
+/// The most query variants `multi_search` runs, to bound the quota a single call can spend
+const MAX_QUERY_VARIANTS: usize = 5;
+
+#[derive(Deserialize, JsonSchema)]
+struct MultiSearchParams {
+    /// 1 to 5 different phrasings of the same question, e.g. ["tokio select in a loop",
+    /// "tokio::select! cancel safety loop"]. Vary the wording and keywords rather than repeating
+    /// the same query
+    queries: Vec<String>,
+    /// the search engines to run every query on, e.g. ["google", "searxng"]. Defaults to the
+    /// server's configured search. Each engine multiplies the number of searches made
+    backends: Option<Vec<BackendKind>>,
+    /// restricts results to pages from the last so many days, weeks, months or years, as in
+    /// query_google_search, e.g. "y1"
+    date_restrict: Option<String>,
+    /// a site (host, optionally with a path) to restrict results to, or to exclude from results
+    site_search: Option<String>,
+    /// whether `site_search` results are the only ones included (the default) or are excluded
+    site_search_filter: Option<SiteSearchFilter>,
+    /// the number of merged results to return, from 1 to 20. Defaults to 10
+    num: Option<u8>,
+    /// how to present the results: "json" (the default) or "text"
+    format: Option<ResultFormat>,
+}
+
+/// The most pages the research tool reads in one call
+const MAX_RESEARCH_PAGES: u8 = 5;
+
+#[derive(Deserialize, JsonSchema)]
+struct ResearchParams {
+    /// the question or search terms, e.g. "tokio select cancel safety in a loop"
+    query: String,
+    /// how many of the top fetchable results to read, from 1 to 5. Defaults to 3
+    pages: Option<u8>,
+    /// the most characters of page content to return, shared among the pages. Defaults to, and
+    /// can't exceed, the server's limit
+    budget: Option<usize>,
+    /// restricts results to pages from the last so many days, weeks, months or years, as in
+    /// query_google_search, e.g. "y1"
+    date_restrict: Option<String>,
+    /// a site (host, optionally with a path) to restrict results to, e.g. "stackoverflow.com"
+    site_search: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SiteSearchFilter {
+    /// only return results from the site
+    Include,
+    /// never return results from the site
+    Exclude,
+}
+
+#[derive(Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SortOrder {
+    Relevance,
+    Date,
+}
+
+impl SearchParams {
+    /// The query string parameters of the Custom Search API for these params, other than the
+    /// engine ID and key
+    fn query_pairs(&self) -> Vec<(&'static str, String)> {
+        let mut pairs = vec![("q", self.query.clone())];
+        let optional = [
+            ("exactTerms", self.exact_terms.clone()),
+            ("excludeTerms", self.exclude_terms.clone()),
+            ("orTerms", self.or_terms.clone()),
+            ("start", self.start.map(|start| start.to_string())),
+            ("num", self.num.map(|num| num.to_string())),
+            ("dateRestrict", self.date_restrict.clone()),
+            ("siteSearch", self.site_search.clone()),
+            (
+                "siteSearchFilter",
+                self.site_search
+                    .as_ref()
+                    .and(self.site_search_filter)
+                    .map(|filter| match filter {
+                        SiteSearchFilter::Include => "i".to_owned(),
+                        SiteSearchFilter::Exclude => "e".to_owned(),
+                    }),
+            ),
+            ("lr", self.lr.clone()),
+            ("gl", self.gl.clone()),
+            (
+                "sort",
+                self.sort.and_then(|sort| match sort {
+                    SortOrder::Relevance => None,
+                    SortOrder::Date => Some("date".to_owned()),
+                }),
+            ),
+            ("fileType", self.file_type.clone()),
+        ];
+        pairs.extend(
+            optional
+                .into_iter()
+                .filter_map(|(name, value)| value.map(|value| (name, value))),
+        );
+        pairs
+    }
+
+    /// Checks the parameters Google would reject, so the model gets an explanation it can act on.
+    /// The same limits are applied to every backend, so results don't depend on which answered
+    fn validate(&self) -> Result<(), ErrorData> {
+        if let Some(num) = self.num
+            && !(1..=10).contains(&num)
+        {
+            return Err(ErrorData::invalid_params(
+                "num must be between 1 and 10",
+                None,
+            ));
+        }
+        // Google never returns results past the 100th
+        if self.start.unwrap_or(1) as usize + self.num.unwrap_or(10) as usize > 101 {
+            return Err(ErrorData::invalid_params(
+                "start + num must not exceed 101, Google only returns the first 100 results",
+                None,
+            ));
+        }
+        if let Some(date_restrict) = self.date_restrict.as_ref()
+            && (!DATE_RESTRICT_RE.is_match(date_restrict)
+                || search::date_restrict_days(date_restrict).is_none())
+        {
+            return Err(ErrorData::invalid_params(
+                "date_restrict must be d, w, m or y followed by a number, e.g. \"y1\"",
+                None,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The ways `fetch_web_page` can retrieve a page
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum FetchHandler {
+    StackOverflow,
+    Mdn,
+    PythonDocs,
+    RustDocs,
+    Docset,
+    Man,
+    Reddit,
+    HackerNews,
+    Discourse,
+    CratesIo,
+    DocsRs,
+    GitHub,
+    Package,
+    Scrapper,
+}
+
+/// The most URLs `fetch_web_pages` takes in one call
+const MAX_BATCH_URLS: usize = 10;
+
+#[derive(Deserialize, JsonSchema)]
+struct FetchPagesParams {
+    /// 1 to 10 urls of supported webpages, such as search results marked as fetchable
+    urls: Vec<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct CrateDocsParams {
+    /// a crate name, optionally followed by @ and a version, and by the :: separated path of an
+    /// item, e.g. "tokio::sync::Mutex" or "serde@1.0.219::de::Deserialize"
+    item: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct PackageInfoParams {
+    /// the registry the package is published to
+    registry: packages::Registry,
+    /// the package name, e.g. "react" or "@types/node" for npm, "requests" for PyPI, a module
+    /// path such as "github.com/spf13/cobra" for Go, or "group:artifact" such as
+    /// "com.google.guava:guava" for Maven
+    name: String,
+    /// the version to describe. Defaults to the latest
+    version: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SearchPythonDocsParams {
+    /// a module, class, function or attribute name, or part of one, e.g. "gather",
+    /// "asyncio.gather" or "pathlib"
+    query: String,
+    /// the Python version, e.g. "3.12". Defaults to the newest available
+    version: Option<String>,
+    /// the number of results to return, from 1 to 50. Defaults to 10
+    num: Option<u8>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LookupRustItemParams {
+    /// the path of a standard library item, or of a method, field or variant of one, e.g.
+    /// "std::sync::Mutex", "std::sync::Mutex::lock" or "core::mem::swap"
+    path: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SearchHnParams {
+    /// the words to look for, e.g. "sqlite production"
+    query: String,
+    /// what to search: "story" (the default), "comment", "ask_hn" or "show_hn"
+    kind: Option<hacker_news::HnKind>,
+    /// restricts results to the last so many days, weeks, months or years, as a letter and a
+    /// number: "d7" is the last week, "y1" the last year
+    date_restrict: Option<String>,
+    /// restricts results to items posted on or after a date, as YYYY-MM-DD
+    after: Option<String>,
+    /// restricts results to items posted before a date, as YYYY-MM-DD
+    before: Option<String>,
+    /// "relevance" (the default) or "date" to put the newest results first
+    sort: Option<SortOrder>,
+    /// the number of results to return, from 1 to 50. Defaults to 10
+    num: Option<u8>,
+}
+
+/// The most results `search_hn` returns
+const MAX_HN_RESULTS: u8 = 50;
+
+#[derive(Deserialize, JsonSchema)]
+struct SearchDocsetsParams {
+    /// a name or part of one, e.g. "std::vector::push_back", "push_back" or "printf"
+    query: String,
+    /// a docset to search, e.g. "cpp" or "python~3.12", where a slug without a version matches
+    /// all its versions. Defaults to all installed docsets
+    docset: Option<String>,
+    /// the number of results to return, from 1 to 50. Defaults to 10
+    num: Option<u8>,
+}
+
+/// The most results `search_docsets` returns
+const MAX_DOCSET_RESULTS: u8 = 50;
+
+#[derive(Deserialize, JsonSchema)]
+struct ManPageParams {
+    /// the page to read, e.g. "tar", "printf" or "systemd.unit"
+    name: Option<String>,
+    /// the section, e.g. "1" for commands, "3" for C library functions or "5" for file
+    /// formats. Defaults to the first section that has the page
+    section: Option<String>,
+    /// a heading of the page to return alone, e.g. "OPTIONS" or "EXAMPLES"
+    heading: Option<String>,
+    /// words to look for in the names and one-line descriptions of all pages, like apropos,
+    /// instead of reading a page
+    search: Option<String>,
+    /// the number of search results to return, from 1 to 50. Defaults to 20
+    num: Option<u8>,
+    /// run the command `name` with --help instead of reading its man page, for commands that
+    /// have none. Only available if the server allows it
+    help: Option<bool>,
+}
+
+/// The most results a `man_page` search returns
+const MAX_MAN_RESULTS: u8 = 50;
+
+/// The most results `search_python_docs` returns
+const MAX_PYTHON_DOCS_RESULTS: u8 = 50;
+
+/// The most results `search_code` returns
+const MAX_CODE_RESULTS: u8 = 20;
+
+#[derive(Deserialize, JsonSchema)]
+struct SearchCodeParams {
+    /// the code or identifiers to look for, optionally with GitHub code search qualifiers, e.g.
+    /// "fn poll_recv" or "\"max_blocking_threads\" extension:rs"
+    query: String,
+    /// a repository to search in, as owner/name, e.g. "tokio-rs/tokio"
+    repo: Option<String>,
+    /// a language to restrict results to, e.g. "rust" or "toml"
+    language: Option<String>,
+    /// a directory or file path results must be under, e.g. "tokio/src/sync"
+    path: Option<String>,
+    /// the number of files to return, from 1 to 20. Defaults to 10
+    num: Option<u8>,
 }
 
 #[derive(Deserialize, JsonSchema)]
 struct FetchPageParams {
-    /// the url of a supported webpage. Must be from a search result or will be invalid
+    /// the url of a supported webpage, such as a search result marked as fetchable. Search
+    /// results that aren't fetchable will be rejected
     url: String,
 }
 
-#[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), anyhow::Error> {
-    let config = Config::parse();
-    let code_tools = Tools::new(config);
-    let service = code_tools.serve(stdio()).await.inspect_err(|e| {
-        println!("error starting server: {}", e);
-    })?;
-    service.waiting().await?;
+fn main() -> Result<(), anyhow::Error> {
+    let config = Config::load()?;
+    let mut builder = match config.worker_threads {
+        1 => tokio::runtime::Builder::new_current_thread(),
+        threads => {
+            let mut builder = tokio::runtime::Builder::new_multi_thread();
+            if threads > 0 {
+                builder.worker_threads(threads);
+            }
+            builder
+        }
+    };
+    builder.enable_all().build()?.block_on(run(config))
+}
+
+async fn run(config: Config) -> Result<(), anyhow::Error> {
+    let transport = config.transport;
+    let listen = config.listen;
+    let allow_unauthenticated = config.allow_unauthenticated;
+    let auth = Authenticator::load(config.auth_tokens_file.as_deref(), &config.auth_tokens)?;
+    let code_tools = Tools::new(config)?;
+    match transport {
+        Transport::Stdio => {
+            let service = code_tools.serve(stdio()).await.inspect_err(|e| {
+                println!("error starting server: {}", e);
+            })?;
+            service.waiting().await?;
+        }
+        Transport::Http => serve_http(code_tools, auth, listen, allow_unauthenticated).await?,
+    }
     Ok(())
 }
 
+/// Serves the tools over streamable HTTP until interrupted. Every session gets a clone of
+/// `tools`, so the cache, HTTP client and reddit session are shared among all clients.
+/// Unless no tokens are configured, the MCP endpoint requires a bearer token
+async fn serve_http(
+    tools: Tools,
+    auth: Authenticator,
+    listen: SocketAddr,
+    allow_unauthenticated: bool,
+) -> Result<(), anyhow::Error> {
+    if auth.is_empty() && !allow_unauthenticated {
+        return Err(anyhow!(
+            "no auth tokens are configured for the http transport: set --auth-tokens or \
+             --auth-tokens-file, or --allow-unauthenticated to serve anyone who can reach {}",
+            listen
+        ));
+    }
+    let service = StreamableHttpService::new(
+        move || Ok(tools.clone()),
+        LocalSessionManager::default().into(),
+        Default::default(),
+    );
+    let mut router = axum::Router::new().nest_service("/mcp", service);
+    if auth.is_empty() {
+        eprintln!(
+            "warning: no auth tokens configured, anyone who can reach {} can use this server",
+            listen
+        );
+    } else {
+        router = router.layer(axum::middleware::from_fn_with_state(
+            Arc::new(auth),
+            auth::require_bearer,
+        ));
+    }
+    // added after the auth layer so health checks don't need a token
+    let router = router.route("/health", axum::routing::get(|| async { "ok" }));
+    let listener = tokio::net::TcpListener::bind(listen)
+        .await
+        .with_context(|| format!("unable to listen on {}", listen))?;
+    eprintln!("serving MCP over streamable HTTP on http://{}/mcp", listen);
+    axum::serve(listener, router)
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+    Ok(())
+}
+
+/// Resolves on ctrl-c, or SIGTERM on unix (which is what container runtimes send)
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    eprintln!("shutting down");
+}
+
+#[derive(Clone)]
 struct Tools {
-    config: Config,
-    reddit_client: roux::Reddit,
+    config: Arc<Config>,
+    http: Arc<HttpClient>,
+    reddit_token: Arc<tokio::sync::Mutex<Option<RedditToken>>>,
+    reddit_user_agent: String,
+    cache: Arc<ResponseCache>,
+    search: Arc<SearchChain>,
+    host_limiter: Arc<HostLimiter>,
+    /// set when a local Python docs tree is configured
+    python_docs: Option<Arc<PythonDocs>>,
+    /// set when the toolchain's docs are configured
+    rust_docs: Option<Arc<RustDocs>>,
+    /// set when offline docsets are configured
+    docsets: Option<Arc<Docsets>>,
+    man: Arc<ManPages>,
     tool_router: ToolRouter<Self>,
 }
 
-#[tool_handler]
 impl rmcp::ServerHandler for Tools {
     fn get_info(&self) -> ServerInfo {
         ServerInfo{
@@ -109,69 +860,362 @@ impl rmcp::ServerHandler for Tools {
 			..Default::default()
 		}
     }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, ErrorData> {
+        if let Some(policy) = token_policy(&context) {
+            if !policy.allows(&request.name) {
+                return Err(ErrorData::invalid_params(
+                    format!("tool {} is not available to {}", request.name, policy.name),
+                    None,
+                ));
+            }
+            policy.check_rate().map_err(|wait| {
+                ErrorData::invalid_request(
+                    format!(
+                        "rate limit exceeded for {}, retry in {} seconds",
+                        policy.name,
+                        wait.as_secs() + 1
+                    ),
+                    None,
+                )
+            })?;
+        }
+        let tcc = ToolCallContext::new(self, request, context);
+        self.tool_router.call(tcc).await
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, ErrorData> {
+        let policy = token_policy(&context);
+        Ok(ListToolsResult::with_all_items(
+            self.tool_router
+                .list_all()
+                .into_iter()
+                .filter(|tool| {
+                    policy
+                        .as_ref()
+                        .is_none_or(|policy| policy.allows(&tool.name))
+                })
+                .collect(),
+        ))
+    }
+}
+
+/// The policy of the bearer token the request was authenticated with, if it came over http
+fn token_policy(context: &RequestContext<RoleServer>) -> Option<Arc<TokenPolicy>> {
+    context
+        .extensions
+        .get::<axum::http::request::Parts>()?
+        .extensions
+        .get::<Arc<TokenPolicy>>()
+        .cloned()
 }
 
 #[tool_router]
 impl Tools {
-    fn new(config: Config) -> Self {
-        Self {
+    fn new(config: Config) -> Result<Self, anyhow::Error> {
+        Ok(Self {
             tool_router: Self::tool_router(),
-            reddit_client: roux::Reddit::new(
-                "linux:nimbus:v0.1.0 (by /u/Keozon)",
-                &config.reddit_client_id,
-                &config.reddit_client_secret,
-            ),
-            config,
-        }
+            http: Arc::new(HttpClient::new(
+                &config.user_agent(),
+                Duration::from_secs(config.http_connect_timeout),
+                Duration::from_secs(config.http_read_timeout),
+                RetryPolicy {
+                    max_retries: config.http_max_retries,
+                    base_delay: Duration::from_millis(500),
+                    max_delay: Duration::from_secs(config.http_retry_max_delay),
+                },
+            )?),
+            reddit_token: Arc::new(tokio::sync::Mutex::new(None)),
+            reddit_user_agent: config.reddit_user_agent(),
+            cache: Arc::new(ResponseCache::new(
+                CacheTtls {
+                    google_search: Duration::from_secs(config.cache_ttl_google),
+                    stack_overflow: Duration::from_secs(config.cache_ttl_stack_overflow),
+                    reddit: Duration::from_secs(config.cache_ttl_reddit),
+                    hacker_news: Duration::from_secs(config.cache_ttl_hacker_news),
+                    discourse: Duration::from_secs(config.cache_ttl_discourse),
+                    crates: Duration::from_secs(config.cache_ttl_crates),
+                    github: Duration::from_secs(config.cache_ttl_github),
+                    packages: Duration::from_secs(config.cache_ttl_packages),
+                    scrapper: Duration::from_secs(config.cache_ttl_scrapper),
+                },
+                config.cache_max_entries,
+                config.cache_dir.clone(),
+            )),
+            search: Arc::new(config.search_chain()?),
+            host_limiter: Arc::new(HostLimiter::new(config.http_per_host_concurrency)),
+            python_docs: config
+                .python_docs_path
+                .clone()
+                .map(|path| Arc::new(PythonDocs::new(path))),
+            rust_docs: config
+                .rust_docs_path
+                .clone()
+                .map(|path| Arc::new(RustDocs::new(path))),
+            docsets: config
+                .docsets_path
+                .clone()
+                .map(|path| Docsets::new(path, &config.docset_urls))
+                .transpose()?
+                .map(Arc::new),
+            man: Arc::new(ManPages::new(&config.man_path)),
+            config: Arc::new(config),
+        })
     }
 
     #[tool(
-        description = "Search a subset of sites in Google for a list of matching web pages with snippets of information"
+        description = "Search a subset of sites on the web for a list of matching web pages with snippets of information. Each result has its rank, title, url, source kind (so, mdn, reddit, forum or other), a snippet, and for Stack Overflow and forums, votes, answer counts or post dates"
     )]
     async fn query_google_search(
         &self,
-        params: Parameters<GoogleSearchParams>,
+        params: Parameters<SearchParams>,
     ) -> Result<CallToolResult, ErrorData> {
-        let client = self.get_http_client();
-        let base_route = "https://customsearch.googleapis.com/customsearch/v1?";
-        let mut builder = client.get(base_route);
-        if let Some(exact_terms) = params.0.exact_terms.as_ref() {
-            builder = builder.query(&[("exactTerms", &exact_terms)]);
-        }
-        if let Some(exclude_terms) = params.0.exclude_terms.as_ref() {
-            builder = builder.query(&[("excludeTerms", &exclude_terms)]);
-        }
-        if let Some(start) = params.0.start {
-            builder = builder.query(&[("start", &format!("{}", start))]);
-        }
-        builder = builder
-            .query(&[("q", &params.0.query)])
-            .query(&[("cx", &self.config.google_search_engine_id)])
-            .query(&[("key", &self.config.google_search_api_key)]);
-        let res = builder
-            .send()
-            .await
-            .map_err(|err| ErrorData::invalid_params(format!("{}", err), None))?;
-        let results: GoogleSearchResults = res
-            .json()
-            .await
-            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
-        let json = serde_json::to_string(&results).unwrap();
-        Ok(CallToolResult::success(vec![Content::text(json)]))
+        let mut results = self.search(&params.0, None).await?;
+        results.mark_fetchable(
+            |url| self.fetch_handler(url),
+            self.config.only_fetchable_results,
+        );
+        let mut contents = vec![Content::text(
+            results.render(params.0.format.unwrap_or_default()),
+        )];
+        contents.extend(self.quota_note(&[None]));
+        Ok(CallToolResult::success(contents))
+    }
+
+    #[tool(
+        description = "Search with several phrasings of the same question at once, and get back one merged list of web pages. Pages found by more than one query rank higher, and duplicates are removed. Prefer this over calling query_google_search repeatedly with similar queries. Results have the same fields as query_google_search"
+    )]
+    async fn multi_search(
+        &self,
+        params: Parameters<MultiSearchParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let params = params.0;
+        if !(1..=MAX_QUERY_VARIANTS).contains(&params.queries.len()) {
+            return Err(ErrorData::invalid_params(
+                format!("queries must have 1 to {} entries", MAX_QUERY_VARIANTS),
+                None,
+            ));
+        }
+        let limit = params.num.unwrap_or(10) as usize;
+        if !(1..=20).contains(&limit) {
+            return Err(ErrorData::invalid_params(
+                "num must be between 1 and 20",
+                None,
+            ));
+        }
+        let backends: Vec<Option<BackendKind>> = match params.backends.as_ref() {
+            Some(kinds) if !kinds.is_empty() => {
+                if let Some(missing) = kinds.iter().find(|kind| !self.search.has(**kind)) {
+                    return Err(ErrorData::invalid_params(
+                        format!(
+                            "the {} search backend isn't configured on this server",
+                            missing.to_possible_value().unwrap().get_name()
+                        ),
+                        None,
+                    ));
+                }
+                kinds.iter().copied().map(Some).collect()
+            }
+            _ => vec![None],
+        };
+        let searches: Vec<(SearchParams, Option<BackendKind>)> = params
+            .queries
+            .iter()
+            .flat_map(|query| {
+                backends.iter().map(|backend| {
+                    (
+                        SearchParams {
+                            query: query.clone(),
+                            date_restrict: params.date_restrict.clone(),
+                            site_search: params.site_search.clone(),
+                            site_search_filter: params.site_search_filter,
+                            ..Default::default()
+                        },
+                        *backend,
+                    )
+                })
+            })
+            .collect();
+        let outcomes = futures::future::join_all(
+            searches
+                .iter()
+                .map(|(search, backend)| self.search(search, *backend)),
+        )
+        .await;
+        let mut lists = Vec::new();
+        let mut last_error = None;
+        for ((search, _), outcome) in searches.iter().zip(outcomes) {
+            match outcome {
+                Ok(results) => lists.push(results),
+                Err(err) => {
+                    eprintln!(
+                        "multi_search query {:?} failed: {}",
+                        search.query, err.message
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+        if lists.is_empty()
+            && let Some(err) = last_error
+        {
+            return Err(err);
+        }
+        let mut results = SearchResults::fuse(lists, limit);
+        results.mark_fetchable(
+            |url| self.fetch_handler(url),
+            self.config.only_fetchable_results,
+        );
+        let mut contents = vec![Content::text(
+            results.render(params.format.unwrap_or_default()),
+        )];
+        contents.extend(self.quota_note(&backends));
+        Ok(CallToolResult::success(contents))
     }
 
-    fn get_http_client(&self) -> reqwest::Client {
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            USER_AGENT,
-            "Nimbus Agent/1.0 (reqwest; zbullough@qmulosoft.com)"
-                .parse()
-                .unwrap(),
+    #[tool(
+        description = "Answer a question in one step: searches the web, reads the top few results that can be fetched, and returns the passage of each most relevant to the query, numbered for citation with its title and url. Use this first for most questions, and fetch_web_page on a cited url if its passage isn't enough"
+    )]
+    async fn research(
+        &self,
+        params: Parameters<ResearchParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let params = params.0;
+        let pages = params.pages.unwrap_or(3);
+        if !(1..=MAX_RESEARCH_PAGES).contains(&pages) {
+            return Err(ErrorData::invalid_params(
+                format!("pages must be between 1 and {}", MAX_RESEARCH_PAGES),
+                None,
+            ));
+        }
+        let budget = params
+            .budget
+            .unwrap_or(self.config.research_budget)
+            .min(self.config.research_budget);
+        let search = SearchParams {
+            query: params.query.clone(),
+            date_restrict: params.date_restrict,
+            site_search: params.site_search,
+            ..Default::default()
+        };
+        let mut results = self.search(&search, None).await?;
+        results.mark_fetchable(|url| self.fetch_handler(url), true);
+        let sources: Vec<_> = results
+            .results
+            .into_iter()
+            .filter_map(|result| Some((Url::parse(&result.url).ok()?, result)))
+            .take(pages as usize)
+            .collect();
+        if sources.is_empty() {
+            let mut contents = vec![Content::text(
+                "No fetchable results. Try query_google_search with other terms",
+            )];
+            contents.extend(self.quota_note(&[None]));
+            return Ok(CallToolResult::success(contents));
+        }
+        let pages =
+            futures::future::join_all(sources.iter().map(|(url, _)| self.fetch_page(url))).await;
+
+        let mut bundle = format!("# Research: {}\n", params.query);
+        let mut failed = Vec::new();
+        let mut remaining = budget;
+        let mut cited = 0;
+        let fetched = pages.iter().filter(|page| page.is_ok()).count();
+        for ((_, result), page) in sources.iter().zip(pages) {
+            match page {
+                Ok(parts) => {
+                    // pages that use less than their share leave more for the ones after them
+                    let share = remaining / (fetched - cited);
+                    let passage = passage::best_passage(&parts.join("\n\n"), &params.query, share);
+                    remaining -= passage.chars().count().min(remaining);
+                    cited += 1;
+                    let _ = write!(
+                        bundle,
+                        "\n[{}] {}\n{}\n\n{}\n",
+                        cited, result.title, result.url, passage
+                    );
+                }
+                Err(err) => failed.push(format!("{} ({})", result.url, err.message)),
+            }
+        }
+        if !failed.is_empty() {
+            let _ = write!(bundle, "\nCouldn't read: {}\n", failed.join(", "));
+        }
+        let mut contents = vec![Content::text(bundle)];
+        contents.extend(self.quota_note(&[None]));
+        Ok(CallToolResult::success(contents))
+    }
+
+    /// A note telling the model the Google quota is running low, if any of the searches'
+    /// backends (`None` for the whole chain) would spend it. Logging it alone would only tell
+    /// the operator, not the client using it up
+    fn quota_note(&self, backends: &[Option<BackendKind>]) -> Option<Content> {
+        backends
+            .iter()
+            .find_map(|only| self.search.quota_warning(*only))
+            .map(|warning| Content::text(format!("Warning: {}", warning)))
+    }
+
+    /// Searches with the configured backends, or `only` the one of that kind, through the cache
+    async fn search(
+        &self,
+        params: &SearchParams,
+        only: Option<BackendKind>,
+    ) -> Result<SearchResults, ErrorData> {
+        params.validate()?;
+        let key = params_key(
+            &self.search.name(only),
+            &params
+                .query_pairs()
+                .into_iter()
+                .map(|(name, value)| (name, Some(value)))
+                .collect::<Vec<_>>(),
         );
-        reqwest::Client::builder()
-            .default_headers(headers)
-            .build()
-            .unwrap()
+        let contents = self
+            .cached(CacheSource::GoogleSearch, key, async {
+                let results = self.search.search(&self.http, params, only).await?;
+                Ok(vec![serde_json::to_string(&results).unwrap()])
+            })
+            .await?;
+        // results are cached normalized, and only rendered in the requested format by tools
+        serde_json::from_str(&contents[0])
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
+    }
+
+    /// Returns the cached response for `key` if there is one, otherwise awaits `fetch` and
+    /// caches its result if it succeeded. Concurrent calls for the same key wait for the first
+    /// one, rather than all hitting the upstream (and spending quota)
+    async fn cached<E>(
+        &self,
+        source: CacheSource,
+        key: String,
+        fetch: impl Future<Output = Result<Vec<String>, E>>,
+    ) -> Result<Vec<String>, E> {
+        if let Some(contents) = self.cache.get(&key) {
+            return Ok(contents);
+        }
+        let _guard = self.cache.lock(&key).await;
+        if let Some(contents) = self.cache.get(&key) {
+            return Ok(contents);
+        }
+        let contents = fetch.await?;
+        self.cache.put(source, key, contents.clone());
+        Ok(contents)
+    }
+
+    /// Runs a fetch from `host` once `HostLimiter` has a slot for it. Fetches are wrapped in it
+    /// inside `cached`, so cache hits and local sources don't wait on a slot
+    async fn limited<T>(&self, host: &str, fetch: impl Future<Output = T>) -> T {
+        let _permit = self.host_limiter.acquire(host).await;
+        fetch.await
     }
 
     async fn fetch_mdn_page(&self, url: Url) -> Result<String, anyhow::Error> {
@@ -180,10 +1224,9 @@ impl Tools {
         // just the URL needs lowercased, and the "docs" part needs removed
         let path = url.path().to_ascii_lowercase().replace("/docs/", "/");
         let full_path = format!("{}{}/index.md", self.config.mdn_base_path, path);
-        let mut file = File::open(full_path).context("unable to find MDN content at path")?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .context("unable to read mdn page file")?;
+        let mut contents = tokio::fs::read_to_string(full_path)
+            .await
+            .context("unable to read MDN content at path")?;
         contents = DOMXREF_RE.replace_all(&contents, "`$arg`").to_string();
         Ok(TEMPLATE_RE.replace_all(&contents, "").to_string())
     }
@@ -193,95 +1236,147 @@ impl Tools {
         raw_submission_id: &str,
     ) -> Result<Vec<String>, anyhow::Error> {
         let submission_id = format!("t3_{}", raw_submission_id);
-        let session = self
-            .reddit_client
-            .clone()
-            .username(&self.config.reddit_username)
-            .password(&self.config.reddit_password)
-            .login()
-            .await?;
-        let mut submission = session.get_submissions(&submission_id).await?;
+        let token = self.reddit_access_token().await?;
+        let res = self
+            .http
+            .send(
+                self.http
+                    .get(format!(
+                        "https://oauth.reddit.com/comments/{}",
+                        raw_submission_id
+                    ))
+                    .header(USER_AGENT, &self.reddit_user_agent)
+                    .bearer_auth(token)
+                    .query(&[("depth", "3"), ("limit", "20")]),
+            )
+            .await
+            .context("fetching submission comments")?
+            .error_for_status()?;
+        // the comments endpoint returns the submission listing followed by the comment listing
+        let (mut submission, comments): (Submissions, Comments) = res.json().await?;
+        if submission.data.children.is_empty() {
+            return Err(anyhow!("reddit returned no submission with this ID"));
+        }
         let submission = submission.data.children.swap_remove(0);
         let title = submission.data.title;
         let contents = submission.data.selftext;
         let likes = submission.data.score;
         let subreddit = submission.data.subreddit;
-        let mut thread: Vec<String> = Vec::new();
         let sub = format!(
             "<h1>{}: {}</h1><p>Score/Likes: {}</p><p>{}</p>",
             subreddit, title, likes, contents
         );
-        thread.push(sub);
-        let comment_client = roux::Subreddit::new_oauth(&subreddit, &session.client);
-        let comments = comment_client
-            .article_comments(&raw_submission_id, Some(3), Some(20))
-            .await
-            .context("fetching submission comments")?;
+        let mut thread = Thread::new(sub, MAX_THREAD_CHARS);
         // use shorter ID names for relationships among comments in this thread
         // this will help smaller models maintain coherence
         let mut contextual_id_map = HashMap::new();
         contextual_id_map.insert(submission_id, 0);
         // TODO make sure the snippet returned from google search is in returned comments
         Self::process_reddit_children(&mut contextual_id_map, &mut thread, comments)?;
-        Ok(thread)
+        Ok(thread.into_parts())
+    }
+
+    /// Returns the current reddit OAuth token, logging in again if we don't have one or it's
+    /// about to expire
+    async fn reddit_access_token(&self) -> Result<String, anyhow::Error> {
+        let mut token = self.reddit_token.lock().await;
+        if let Some(current) = token.as_ref()
+            && current.expires_at > Instant::now()
+        {
+            return Ok(current.access_token.clone());
+        }
+        let res = self
+            .http
+            .send(
+                self.http
+                    .post("https://www.reddit.com/api/v1/access_token")
+                    .header(USER_AGENT, &self.reddit_user_agent)
+                    .basic_auth(
+                        &self.config.reddit_client_id,
+                        Some(&self.config.reddit_client_secret),
+                    )
+                    .form(&[
+                        ("grant_type", "password"),
+                        ("username", &self.config.reddit_username),
+                        ("password", &self.config.reddit_password),
+                    ]),
+            )
+            .await
+            .context("logging in to reddit")?
+            .error_for_status()?;
+        let auth: RedditAuthResponse = res.json().await?;
+        let access_token = match auth {
+            RedditAuthResponse::Token {
+                access_token,
+                expires_in,
+            } => {
+                // refresh a minute early so a token never expires mid-request
+                *token = Some(RedditToken {
+                    access_token: access_token.clone(),
+                    expires_at: Instant::now() + Duration::from_secs(expires_in.saturating_sub(60)),
+                });
+                access_token
+            }
+            RedditAuthResponse::Error { error } => {
+                return Err(anyhow!("reddit login failed: {}", error));
+            }
+        };
+        Ok(access_token)
     }
 
     fn process_reddit_children(
         contextual_id_map: &mut HashMap<String, usize>,
-        thread: &mut Vec<String>,
+        thread: &mut Thread,
         comments: BasicThing<Listing<BasicThing<CommentData>>>,
     ) -> Result<(), anyhow::Error> {
         for comment in comments.data.children.into_iter() {
             let id = comment.data.name.unwrap(); // How could this be null?
             contextual_id_map.insert(id.clone(), contextual_id_map.len());
             if let Some(body) = comment.data.body {
-                let id = contextual_id_map.get(&id).unwrap();
-                let user = comment.data.author.unwrap_or("unknown redditor".into());
-                let link = if let Some(link) = comment.data.permalink {
+                let id = *contextual_id_map.get(&id).unwrap();
+                let author = comment.data.author.unwrap_or("unknown redditor".into());
+                let details = if let Some(link) = comment.data.permalink {
                     format!("<a href='{}'>Comment Permalink</a>", link)
                 } else {
-                    format!("")
+                    String::new()
                 };
-                let response_to = if let Some(parent) = comment.data.parent_id {
-                    let parent = contextual_id_map.get(&parent).unwrap_or(&0);
-                    format!(" In response to: {}", parent)
-                } else {
-                    format!("")
-                };
-                thread.push(format!(
-                    "<h1>Comment: #{} from {}{}</h1>{}<p>{}</p>",
-                    id, user, response_to, link, body
-                ))
-            }
-            if let Some(replies) = comment.data.replies {
-                match replies {
-                    MaybeReplies::Reply(replies) => {
-                        Self::process_reddit_children(contextual_id_map, thread, replies)?;
-                    }
-                    _ => {}
-                }
+                let parent = comment
+                    .data
+                    .parent_id
+                    .map(|parent| *contextual_id_map.get(&parent).unwrap_or(&0));
+                thread.push(Comment {
+                    id,
+                    author,
+                    parent,
+                    details,
+                    body,
+                });
+            }
+            if let Some(MaybeReplies::Reply(replies)) = comment.data.replies {
+                Self::process_reddit_children(contextual_id_map, thread, replies)?;
             }
         }
         Ok(())
     }
 
     async fn scrape_other_page(&self, url: &Url) -> Result<String, anyhow::Error> {
-        let client = self.get_http_client();
         let article_path = format!(
             "{}/api/article",
             self.config.scrapper_host.as_ref().unwrap()
         );
-        let res = client
-            .get(article_path)
-            .query(&[("url", url.to_string()), ("timeout", "10000".to_string())])
-            .send()
+        let res = self
+            .http
+            .send(
+                self.http
+                    .get(article_path)
+                    .query(&[("url", url.to_string()), ("timeout", "10000".to_string())]),
+            )
             .await?;
         let article: ScrapperArticle = res.json().await?;
         Ok(article.text_content)
     }
 
     async fn fetch_so_page(&self, question_id: &str) -> Result<Vec<String>, anyhow::Error> {
-        let client = self.get_http_client();
         let so_questions_path = format!(
             "{}/questions/{}",
             self.config.stack_overflow_api_prefix, question_id
@@ -297,20 +1392,18 @@ impl Tools {
         if let Some(ref key) = self.config.stack_overflow_api_key {
             params.push(("key", key.clone()));
         }
-        let res = client
-            .get(so_questions_path)
-            .query(&params)
-            .send()
+        let res = self
+            .http
+            .send(self.http.get(so_questions_path).query(&params))
             .await
             .context("failed to retrieve so question")?;
         let mut question: StackExchangeResponse = res.json().await?;
-        if question.items.len() == 0 {
+        if question.items.is_empty() {
             return Err(anyhow!("SO returned no questions with this ID"));
         }
-        let res = client
-            .get(so_answers_path)
-            .query(&params)
-            .send()
+        let res = self
+            .http
+            .send(self.http.get(so_answers_path).query(&params))
             .await
             .context("failed to retrieve so answers")?;
         let answers: StackExchangeResponse = res.json().await?;
@@ -357,68 +1450,723 @@ impl Tools {
     ) -> Result<CallToolResult, ErrorData> {
         let parsed = Url::from_str(&params.0.url)
             .map_err(|_| ErrorData::invalid_params("failed to parse url as URL", None))?;
-        match parsed.host_str() {
-            Some(host) => {
-                match host {
-                    "stackoverflow.com" => {
-                        eprintln!("{}", parsed);
-                        let question_id: &str = parsed.path_segments().unwrap().nth(1).ok_or(
-                            ErrorData::invalid_params(
-                                "invalid stack overflow URL: missing question id",
-                                None,
-                            ),
-                        )?;
-                        Ok(CallToolResult::success(
-                            self.fetch_so_page(question_id)
-                                .await
-                                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?
-                                .into_iter()
-                                .map(|qa| Content::text(qa))
-                                .collect(),
-                        ))
-                    }
-                    "developer.mozilla.org" => Ok(CallToolResult::success(vec![Content::text(
-                        self.fetch_mdn_page(parsed)
-                            .await
-                            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?,
-                    )])),
-                    "www.reddit.com" => {
-                        let submissision_id = parsed.path_segments().unwrap().nth(3).ok_or(
-                            ErrorData::invalid_params(
-                                "invalid reddit URL: missing comment/submission id in path",
-                                None,
-                            ),
-                        )?;
-                        Ok(CallToolResult::success(
-                            self.fetch_reddit_page(submissision_id)
-                                .await
-                                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?
-                                .into_iter()
-                                .map(|comment| Content::text(comment))
-                                .collect(),
-                        ))
-                    }
-                    _ if self.config.scrapper_host.is_some() => {
-                        Ok(CallToolResult::success(vec![Content::text(
-                            self.scrape_other_page(&parsed).await.map_err(|err| {
-                                ErrorData::internal_error(format!("{}", err), None)
-                            })?,
-                        )]))
-                    }
-                    _ => Err(ErrorData::invalid_params(
-                        format!(
-                            "invalid host: {}. Must be from provided search results",
-                            host
-                        ),
+        Ok(CallToolResult::success(
+            self.fetch_page(&parsed)
+                .await?
+                .into_iter()
+                .map(Content::text)
+                .collect(),
+        ))
+    }
+
+    #[tool(
+        description = "Retrieve the primary contents of several webpages at once, e.g. the fetchable results of a search. Each page's contents, or the reason it couldn't be fetched, is returned under a heading with its url. A page failing doesn't affect the others"
+    )]
+    async fn fetch_web_pages(
+        &self,
+        params: Parameters<FetchPagesParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let urls = params.0.urls;
+        if !(1..=MAX_BATCH_URLS).contains(&urls.len()) {
+            return Err(ErrorData::invalid_params(
+                format!("urls must have 1 to {} entries", MAX_BATCH_URLS),
+                None,
+            ));
+        }
+        let pages = futures::future::join_all(urls.iter().map(|url| async move {
+            let parsed = Url::from_str(url)
+                .map_err(|_| ErrorData::invalid_params("failed to parse url as URL", None))?;
+            self.fetch_page(&parsed).await
+        }))
+        .await;
+        Ok(CallToolResult::success(
+            urls.iter()
+                .zip(pages)
+                .map(|(url, page)| {
+                    Content::text(match page {
+                        Ok(parts) => format!("# {}\n\n{}", url, parts.join("\n\n")),
+                        Err(err) => format!("# {}\n\nUnable to fetch: {}", url, err.message),
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    /// Fetches `url` with its handler, as one or more parts (e.g. a question and its answers).
+    /// Fetches that miss the cache wait for a free slot if too many requests to the host are in flight
+    async fn fetch_page(&self, url: &Url) -> Result<Vec<String>, ErrorData> {
+        let host = url.host_str().unwrap_or_default();
+        match self.fetch_handler(url) {
+            Some(FetchHandler::StackOverflow) => {
+                eprintln!("{}", url);
+                let question_id: &str =
+                    url.path_segments()
+                        .unwrap()
+                        .nth(1)
+                        .ok_or(ErrorData::invalid_params(
+                            "invalid stack overflow URL: missing question id",
+                            None,
+                        ))?;
+                self.cached(
+                    CacheSource::StackOverflow,
+                    format!("so:{}", question_id),
+                    self.limited(host, self.fetch_so_page(question_id)),
+                )
+                .await
+                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
+            }
+            Some(FetchHandler::Mdn) => {
+                Ok(vec![self.fetch_mdn_page(url.clone()).await.map_err(
+                    |err| ErrorData::internal_error(format!("{}", err), None),
+                )?])
+            }
+            Some(FetchHandler::PythonDocs) => {
+                let python_docs = self.python_docs.as_ref().unwrap();
+                Ok(vec![python_docs.page(url).await.map_err(|err| {
+                    ErrorData::internal_error(format!("{}", err), None)
+                })?])
+            }
+            Some(FetchHandler::RustDocs) => {
+                let rust_docs = self.rust_docs.as_ref().unwrap();
+                rust_docs
+                    .page(url)
+                    .await
+                    .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
+            }
+            Some(FetchHandler::Docset) => {
+                let docsets = self.docsets.as_ref().unwrap();
+                Ok(vec![docsets.page(url).await.map_err(|err| {
+                    ErrorData::internal_error(format!("{}", err), None)
+                })?])
+            }
+            Some(FetchHandler::Man) => {
+                let (name, section) = man::man7_page(url).unwrap();
+                Ok(vec![
+                    self.man
+                        .page(&name, Some(&section), None)
+                        .await
+                        .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?,
+                ])
+            }
+            Some(FetchHandler::Reddit) => {
+                let submissision_id =
+                    url.path_segments()
+                        .unwrap()
+                        .nth(3)
+                        .ok_or(ErrorData::invalid_params(
+                            "invalid reddit URL: missing comment/submission id in path",
+                            None,
+                        ))?;
+                self.cached(
+                    CacheSource::Reddit,
+                    format!("reddit:{}", submissision_id),
+                    self.limited(host, self.fetch_reddit_page(submissision_id)),
+                )
+                .await
+                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
+            }
+            Some(FetchHandler::HackerNews) => {
+                let id = hacker_news::item_id(url).unwrap();
+                self.cached(
+                    CacheSource::HackerNews,
+                    format!("hn:{}", id),
+                    self.limited(host, hacker_news::fetch_item(&self.http, id)),
+                )
+                .await
+                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
+            }
+            Some(FetchHandler::Discourse) => {
+                let (Some(host), Some(topic_id)) = (url.host_str(), discourse::topic_id(url))
+                else {
+                    return Err(ErrorData::invalid_params(
+                        "invalid discourse URL: missing topic id",
+                        None,
+                    ));
+                };
+                self.cached(
+                    CacheSource::Discourse,
+                    format!("discourse:{}:{}", host, topic_id),
+                    self.limited(host, discourse::fetch_topic(&self.http, host, topic_id)),
+                )
+                .await
+                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
+            }
+            Some(FetchHandler::CratesIo) => {
+                let Some((krate, version)) = crates::crates_io_crate(url) else {
+                    return Err(ErrorData::invalid_params(
+                        "invalid crates.io URL: missing crate name",
                         None,
-                    )),
+                    ));
+                };
+                self.crate_info(&krate, version.as_deref()).await
+            }
+            Some(FetchHandler::DocsRs) => {
+                let mut segments = url.path_segments().into_iter().flatten();
+                // docs.rs/crate/{name}/{version} is an overview of the crate, not its docs
+                if segments.next() == Some("crate")
+                    && let Some(krate) = segments.next()
+                {
+                    let version = segments.next().filter(|version| *version != "latest");
+                    return self.crate_info(krate, version).await;
                 }
+                self.docs_page(url.as_str()).await
+            }
+            Some(FetchHandler::GitHub) => {
+                let Some(page) = github::GitHubPage::parse(url) else {
+                    return Err(ErrorData::invalid_params(
+                        "invalid GitHub URL: must be a repo, issue, pull request, discussion or file",
+                        None,
+                    ));
+                };
+                let github = github::GitHub {
+                    http: &self.http,
+                    token: self.config.github_token.as_deref(),
+                };
+                self.cached(
+                    CacheSource::GitHub,
+                    format!("github:{}", page.key()),
+                    self.limited(host, github.fetch(&page)),
+                )
+                .await
+                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))
             }
-            None => Err(ErrorData::invalid_params(
-                "invalid URL: no host component",
+            Some(FetchHandler::Package) => {
+                let Some((registry, name, version)) = packages::package_url(url) else {
+                    return Err(ErrorData::invalid_params(
+                        "invalid package URL: missing package name",
+                        None,
+                    ));
+                };
+                self.package(registry, &name, version.as_deref()).await
+            }
+            Some(FetchHandler::Scrapper) => self
+                .cached(
+                    CacheSource::Scrapper,
+                    format!("scrapper:{}", canonical_url(url)),
+                    self.limited(host, async { Ok(vec![self.scrape_other_page(url).await?]) }),
+                )
+                .await
+                .map_err(|err: anyhow::Error| ErrorData::internal_error(format!("{}", err), None)),
+            None => match url.host_str() {
+                Some(host) => Err(ErrorData::invalid_params(
+                    format!(
+                        "invalid host: {}. Must be from provided search results",
+                        host
+                    ),
+                    None,
+                )),
+                None => Err(ErrorData::invalid_params(
+                    "invalid URL: no host component",
+                    None,
+                )),
+            },
+        }
+    }
+
+    #[tool(
+        description = "Look up a Rust crate, or an item in its documentation. Give a crate name for its crates.io metadata (versions, MSRV, features, downloads, repository) and the docs of its root module, or a path for the docs of one item. E.g. \"tokio\", \"tokio@1.47.1\", \"tokio::sync::Mutex\" or \"serde@1.0.219::de::Deserialize\". Versions default to the latest"
+    )]
+    async fn crate_docs(
+        &self,
+        params: Parameters<CrateDocsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let spec = crates::ItemSpec::parse(&params.0.item).ok_or_else(|| {
+            ErrorData::invalid_params(
+                "item must be a crate name (letters, digits, _ and -), optionally followed by @version and a ::path",
                 None,
-            )),
+            )
+        })?;
+        // all.html runs to megabytes for large crates, so the page it resolves to is cached
+        let url = self
+            .cached(
+                CacheSource::Crates,
+                format!("docs-item:{}", params.0.item.trim()),
+                self.limited("docs.rs", async {
+                    Ok(vec![crates::resolve_item(&self.http, &spec).await?])
+                }),
+            )
+            .await
+            .map_err(|err: anyhow::Error| ErrorData::internal_error(format!("{}", err), None))?
+            .concat();
+        let mut parts = Vec::new();
+        if spec.path.is_empty() {
+            parts.extend(
+                self.crate_info(&spec.krate, spec.version.as_deref())
+                    .await?,
+            );
+        }
+        parts.extend(self.docs_page(&url).await?);
+        Ok(CallToolResult::success(
+            parts.into_iter().map(Content::text).collect(),
+        ))
+    }
+
+    #[tool(
+        description = "Search source code on GitHub. Returns the matching files, each with its repository, path, url and the matching fragments. Use it to find how a library implements something or where a config key is read, then fetch_web_page a file's url (with a #L10-L40 fragment for a range of lines) to read it"
+    )]
+    async fn search_code(
+        &self,
+        params: Parameters<SearchCodeParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let params = params.0;
+        let num = params.num.unwrap_or(10);
+        if num == 0 || num > MAX_CODE_RESULTS {
+            return Err(ErrorData::invalid_params(
+                format!("num must be between 1 and {}", MAX_CODE_RESULTS),
+                None,
+            ));
+        }
+        let mut query = params.query.trim().to_owned();
+        for (qualifier, value) in [
+            ("repo", &params.repo),
+            ("language", &params.language),
+            ("path", &params.path),
+        ] {
+            if let Some(value) = value {
+                let _ = write!(query, " {}:{}", qualifier, value);
+            }
+        }
+        let github = github::GitHub {
+            http: &self.http,
+            token: self.config.github_token.as_deref(),
+        };
+        let parts = self
+            .cached(
+                CacheSource::GitHub,
+                format!("github:search:{}:{}", query, num),
+                github.search_code(&query, num),
+            )
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+        Ok(CallToolResult::success(
+            parts.into_iter().map(Content::text).collect(),
+        ))
+    }
+
+    #[tool(
+        description = "Look up a package on npm, PyPI, the Go module proxy or Maven Central. Returns the latest and recent versions with release dates, deprecation notices, license, repository, dependencies and README, so version and compatibility questions get current answers"
+    )]
+    async fn package_info(
+        &self,
+        params: Parameters<PackageInfoParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let params = params.0;
+        let parts = self
+            .package(params.registry, &params.name, params.version.as_deref())
+            .await?;
+        Ok(CallToolResult::success(
+            parts.into_iter().map(Content::text).collect(),
+        ))
+    }
+
+    async fn package(
+        &self,
+        registry: packages::Registry,
+        name: &str,
+        version: Option<&str>,
+    ) -> Result<Vec<String>, ErrorData> {
+        self.cached(
+            CacheSource::Packages,
+            format!(
+                "packages:{}:{}@{}",
+                registry.name(),
+                name,
+                version.unwrap_or("")
+            ),
+            self.limited(registry.host(), async {
+                Ok(vec![
+                    packages::fetch_package(&self.http, registry, name, version).await?,
+                ])
+            }),
+        )
+        .await
+        .map_err(|err: anyhow::Error| ErrorData::internal_error(format!("{}", err), None))
+    }
+
+    #[tool(
+        description = "Search the Python standard library docs by module, class, function or attribute name. Returns each match with its kind and docs.python.org url. Fetch the url with fetch_web_page to read just that object's documentation"
+    )]
+    async fn search_python_docs(
+        &self,
+        params: Parameters<SearchPythonDocsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let Some(python_docs) = self.python_docs.as_ref() else {
+            return Err(ErrorData::invalid_params(
+                "no local Python docs are configured on this server",
+                None,
+            ));
+        };
+        let params = params.0;
+        let num = params.num.unwrap_or(10);
+        if num == 0 || num > MAX_PYTHON_DOCS_RESULTS {
+            return Err(ErrorData::invalid_params(
+                format!("num must be between 1 and {}", MAX_PYTHON_DOCS_RESULTS),
+                None,
+            ));
         }
+        let results = python_docs
+            .search(&params.query, params.version.as_deref(), num as usize)
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+        let text = if results.is_empty() {
+            format!("no Python docs entries match {}", params.query)
+        } else {
+            results.join("\n")
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Look up an item of the Rust standard library (std, core, alloc, proc_macro or test) in the docs of the server's pinned toolchain, e.g. \"std::sync::Mutex\" or \"std::sync::Mutex::lock\". Returns its signature, stability, docs, and for types their methods and trait implementations. Prefer it over the online docs, which may describe a newer Rust"
+    )]
+    async fn lookup_rust_item(
+        &self,
+        params: Parameters<LookupRustItemParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let Some(rust_docs) = self.rust_docs.as_ref() else {
+            return Err(ErrorData::invalid_params(
+                "no local Rust docs are configured on this server",
+                None,
+            ));
+        };
+        let parts = rust_docs
+            .lookup(&params.0.path)
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+        Ok(CallToolResult::success(
+            parts.into_iter().map(Content::text).collect(),
+        ))
+    }
+
+    #[tool(
+        description = "Search Hacker News stories or comments, optionally within a date range. Returns each story with its points, comment count, date and url. Fetch a news.ycombinator.com url with fetch_web_page to read its discussion, useful for experience reports such as whether a tool is production ready"
+    )]
+    async fn search_hn(
+        &self,
+        params: Parameters<SearchHnParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let params = params.0;
+        let num = params.num.unwrap_or(10);
+        if num == 0 || num > MAX_HN_RESULTS {
+            return Err(ErrorData::invalid_params(
+                format!("num must be between 1 and {}", MAX_HN_RESULTS),
+                None,
+            ));
+        }
+        let date = |name: &str, date: &Option<String>| {
+            date.as_deref()
+                .map(|date| {
+                    hacker_news::parse_date(date).ok_or_else(|| {
+                        ErrorData::invalid_params(
+                            format!("{} must be a date as YYYY-MM-DD", name),
+                            None,
+                        )
+                    })
+                })
+                .transpose()
+        };
+        let mut after = date("after", &params.after)?;
+        let before = date("before", &params.before)?;
+        if let Some(date_restrict) = params.date_restrict.as_deref() {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let since = restrict_start(date_restrict, now)?;
+            after = Some(after.map_or(since, |after| after.max(since)));
+        }
+        let search = hacker_news::HnSearch {
+            query: &params.query,
+            kind: params.kind.unwrap_or(hacker_news::HnKind::Story),
+            after,
+            before,
+            by_date: matches!(params.sort, Some(SortOrder::Date)),
+            num,
+        };
+        let key = format!(
+            "hn:search:{}:{:?}:{:?}:{:?}:{}:{}",
+            search.query, search.kind, params.after, params.before, search.by_date, num
+        );
+        let key = match params.date_restrict.as_deref() {
+            // relative ranges move with time, so are keyed by the day they start
+            Some(_) => format!("{}:{:?}", key, after.map(|after| after / (24 * 60 * 60))),
+            None => key,
+        };
+        let parts = self
+            .cached(
+                CacheSource::HackerNews,
+                key,
+                hacker_news::search(&self.http, &search),
+            )
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+        Ok(CallToolResult::success(
+            parts.into_iter().map(Content::text).collect(),
+        ))
+    }
+
+    #[tool(
+        description = "Search the offline docsets installed on the server (DevDocs bundles such as cppreference's C and C++ docs) by name, e.g. \"std::vector::push_back\" or \"printf\". Returns each match with its kind, docset and url. Fetch the url with fetch_web_page to read the entry's documentation"
+    )]
+    async fn search_docsets(
+        &self,
+        params: Parameters<SearchDocsetsParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let Some(docsets) = self.docsets.as_ref() else {
+            return Err(ErrorData::invalid_params(
+                "no offline docsets are configured on this server",
+                None,
+            ));
+        };
+        let params = params.0;
+        let num = params.num.unwrap_or(10);
+        if num == 0 || num > MAX_DOCSET_RESULTS {
+            return Err(ErrorData::invalid_params(
+                format!("num must be between 1 and {}", MAX_DOCSET_RESULTS),
+                None,
+            ));
+        }
+        let results = docsets
+            .search(&params.query, params.docset.as_deref(), num as usize)
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+        let text = if results.is_empty() {
+            format!("no docset entries match {}", params.query)
+        } else {
+            results.join("\n")
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    #[tool(
+        description = "Read a man page installed on the server, rendered as plain text, e.g. name \"tar\", or name \"printf\" with section \"3\". Long pages are truncated, so ask for one heading such as \"OPTIONS\" to read it whole. With search instead of name, finds pages whose names or one-line descriptions contain all the given words, like apropos. With help, runs the command with --help instead, for commands without a man page, if the server allows it"
+    )]
+    async fn man_page(
+        &self,
+        params: Parameters<ManPageParams>,
+    ) -> Result<CallToolResult, ErrorData> {
+        let params = params.0;
+        let text = match (params.name, params.search) {
+            (Some(name), _) if params.help.unwrap_or(false) => {
+                if !self.config.man_run_help {
+                    return Err(ErrorData::invalid_params(
+                        "running commands with --help is disabled on this server",
+                        None,
+                    ));
+                }
+                man::command_help(&name)
+                    .await
+                    .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?
+            }
+            (Some(name), _) => self
+                .man
+                .page(&name, params.section.as_deref(), params.heading.as_deref())
+                .await
+                .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?,
+            (None, Some(search)) => {
+                let num = params.num.unwrap_or(20);
+                if num == 0 || num > MAX_MAN_RESULTS {
+                    return Err(ErrorData::invalid_params(
+                        format!("num must be between 1 and {}", MAX_MAN_RESULTS),
+                        None,
+                    ));
+                }
+                let results = self
+                    .man
+                    .apropos(&search, params.section.as_deref(), num as usize)
+                    .await
+                    .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+                if results.is_empty() {
+                    format!("no man pages match {}", search)
+                } else {
+                    results.join("\n")
+                }
+            }
+            (None, None) => {
+                return Err(ErrorData::invalid_params(
+                    "either name or search is required",
+                    None,
+                ));
+            }
+        };
+        Ok(CallToolResult::success(vec![Content::text(text)]))
+    }
+
+    async fn crate_info(
+        &self,
+        krate: &str,
+        version: Option<&str>,
+    ) -> Result<Vec<String>, ErrorData> {
+        self.cached(
+            CacheSource::Crates,
+            format!("crates:{}@{}", krate, version.unwrap_or("")),
+            self.limited("crates.io", async {
+                Ok(vec![crates::fetch_crate(&self.http, krate, version).await?])
+            }),
+        )
+        .await
+        .map_err(|err: anyhow::Error| ErrorData::internal_error(format!("{}", err), None))
+    }
+
+    async fn docs_page(&self, url: &str) -> Result<Vec<String>, ErrorData> {
+        self.cached(
+            CacheSource::Crates,
+            format!("docs:{}", url),
+            self.limited("docs.rs", async {
+                Ok(vec![crates::fetch_docs_page(&self.http, url).await?])
+            }),
+        )
+        .await
+        .map_err(|err: anyhow::Error| ErrorData::internal_error(format!("{}", err), None))
+    }
+
+    /// The handler `fetch_web_page` would use for `url`, or None if it can't be fetched with the
+    /// current configuration
+    fn fetch_handler(&self, url: &Url) -> Option<FetchHandler> {
+        match url.host_str()? {
+            "stackoverflow.com" => Some(FetchHandler::StackOverflow),
+            "developer.mozilla.org" => Some(FetchHandler::Mdn),
+            "docs.python.org" if self.python_docs.is_some() => Some(FetchHandler::PythonDocs),
+            "doc.rust-lang.org" if self.rust_docs.is_some() => Some(FetchHandler::RustDocs),
+            // pages that aren't installed are left to the scrapper
+            "man7.org"
+                if man::man7_page(url)
+                    .is_some_and(|(name, section)| self.man.installed(&name, &section)) =>
+            {
+                Some(FetchHandler::Man)
+            }
+            // old.reddit.com and bare reddit.com URLs have the same paths as www ones
+            "www.reddit.com" | "old.reddit.com" | "reddit.com" => Some(FetchHandler::Reddit),
+            "news.ycombinator.com" if hacker_news::item_id(url).is_some() => {
+                Some(FetchHandler::HackerNews)
+            }
+            "crates.io" if crates::crates_io_crate(url).is_some() => Some(FetchHandler::CratesIo),
+            "docs.rs" => Some(FetchHandler::DocsRs),
+            "github.com" | "raw.githubusercontent.com"
+                if github::GitHubPage::parse(url).is_some() =>
+            {
+                Some(FetchHandler::GitHub)
+            }
+            _ if packages::package_url(url).is_some() => Some(FetchHandler::Package),
+            _ if self
+                .docsets
+                .as_ref()
+                .is_some_and(|docsets| docsets.page_path(url).is_some()) =>
+            {
+                Some(FetchHandler::Docset)
+            }
+            host if self.config.discourse_hosts.iter().any(|h| h == host)
+                && discourse::topic_id(url).is_some() =>
+            {
+                Some(FetchHandler::Discourse)
+            }
+            _ if self.config.scrapper_host.is_some() => Some(FetchHandler::Scrapper),
+            _ => None,
+        }
+    }
+}
+
+struct RedditToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RedditAuthResponse {
+    Token {
+        access_token: String,
+        expires_in: u64,
+    },
+    Error {
+        error: String,
+    },
+}
+
+/// The start of a `date_restrict` range ending at `now`, both in seconds since the Unix epoch
+fn restrict_start(date_restrict: &str, now: u64) -> Result<u64, ErrorData> {
+    search::date_restrict_days(date_restrict)
+        .filter(|_| DATE_RESTRICT_RE.is_match(date_restrict))
+        .and_then(|days| days.checked_mul(24 * 60 * 60))
+        .map(|seconds| now.saturating_sub(seconds))
+        .ok_or_else(|| {
+            ErrorData::invalid_params(
+                "date_restrict must be d, w, m or y followed by a number, e.g. \"y1\"",
+                None,
+            )
+        })
+}
+
+/// The body of a failed Google API request
+#[derive(Deserialize)]
+struct GoogleErrorResponse {
+    error: GoogleError,
+}
+
+#[derive(Deserialize)]
+struct GoogleError {
+    message: String,
+    /// e.g. RESOURCE_EXHAUSTED or INVALID_ARGUMENT
+    status: Option<String>,
+    #[serde(default)]
+    errors: Vec<GoogleErrorDetail>,
+}
+
+#[derive(Deserialize)]
+struct GoogleErrorDetail {
+    /// e.g. dailyLimitExceeded or keyInvalid
+    reason: Option<String>,
+}
+
+/// Turns a failed Custom Search response into an error the model can act on. Quota and
+/// configuration problems in particular won't go away by retrying, so the model is told so
+fn google_error(status: reqwest::StatusCode, body: &str) -> ErrorData {
+    let error = serde_json::from_str::<GoogleErrorResponse>(body)
+        .map(|response| response.error)
+        .unwrap_or_else(|_| GoogleError {
+            message: body.chars().take(200).collect(),
+            status: None,
+            errors: Vec::new(),
+        });
+    let has_reason = |reasons: &[&str]| {
+        error.errors.iter().any(|detail| {
+            detail
+                .reason
+                .as_deref()
+                .is_some_and(|reason| reasons.contains(&reason))
+        })
+    };
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        || error.status.as_deref() == Some("RESOURCE_EXHAUSTED")
+        || has_reason(&[
+            "dailyLimitExceeded",
+            "quotaExceeded",
+            "rateLimitExceeded",
+            "userRateLimitExceeded",
+        ])
+    {
+        ErrorData::internal_error(
+            "The Google search quota is used up for today. Do not call query_google_search \
+             again. Use fetch_web_page on URLs you already know, or answer from what you have",
+            None,
+        )
+    } else if status == reqwest::StatusCode::FORBIDDEN
+        || has_reason(&["keyInvalid", "accessNotConfigured", "invalid"])
+        || error.status.as_deref() == Some("INVALID_ARGUMENT")
+    {
+        ErrorData::internal_error(
+            format!(
+                "Google search is misconfigured on this server and can't be used until it's \
+                 fixed: {}",
+                error.message
+            ),
+            None,
+        )
+    } else {
+        ErrorData::internal_error(
+            format!("Google search failed ({}): {}", status, error.message),
+            None,
+        )
     }
 }
 
@@ -461,6 +2209,7 @@ struct GoogleSearchPage {
 
 #[derive(Deserialize, Serialize, Debug)]
 #[serde(untagged)]
+#[allow(clippy::upper_case_acronyms)]
 enum PageMap {
     ForumPost(ForumPageMap),
     StackOverflow(StackOverflowPageMap),
@@ -511,6 +2260,9 @@ struct ForumPosting {
 struct StackOverflowPageMap {
     question: Vec<StackOverflowQuestion>,
     answer: Vec<StackOverflowAnswer>,
+    /// only present on some results, most only list answers without saying which is accepted
+    #[serde(rename = "acceptedanswer")]
+    accepted_answer: Option<Vec<StackOverflowAnswer>>,
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -526,6 +2278,8 @@ struct StackOverflowAnswer {
 struct StackOverflowQuestion {
     #[serde(rename = "upvotecount")]
     upvote_count: String,
+    #[serde(rename = "answercount")]
+    answer_count: Option<String>,
     name: String,
     text: String,
 }
@@ -651,6 +2405,136 @@ mod test {
         ));
     }
 
+    #[test]
+    fn test_user_agents() {
+        let required = [
+            "coding-research-tools",
+            "--google-search-engine-id=cx",
+            "--google-search-api-key=key",
+            "--stack-overflow-api-prefix=https://api.stackexchange.com/2.3",
+            "--mdn-base-path=mdn",
+            "--reddit-client-id=id",
+            "--reddit-client-secret=secret",
+            "--reddit-username=someone",
+            "--reddit-password=password",
+        ];
+        let config = Config::try_parse_from(required).unwrap();
+        assert_eq!(
+            config.user_agent(),
+            format!("coding-research-tools/{}", env!("CARGO_PKG_VERSION"))
+        );
+        assert!(config.reddit_user_agent().ends_with("(by /u/someone)"));
+
+        let config = Config::try_parse_from(
+            required
+                .into_iter()
+                .chain(["--contact=ops@example.com", "--reddit-user-agent=custom"]),
+        )
+        .unwrap();
+        assert!(config.user_agent().ends_with("(+ops@example.com)"));
+        assert_eq!(config.reddit_user_agent(), "custom");
+    }
+
+    #[test]
+    fn test_config_file_keys() {
+        let command = Config::command();
+        let ids: Vec<&str> = command
+            .get_arguments()
+            .map(|arg| arg.get_id().as_str())
+            .collect();
+        for (_, _, id) in config_file::KEYS.iter() {
+            assert!(
+                ids.contains(id),
+                "config file key for unknown argument {}",
+                id
+            );
+        }
+        for secret in config_file::SECRETS.iter() {
+            assert!(ids.contains(&format!("{}_file", secret).as_str()));
+        }
+    }
+
+    #[test]
+    fn test_google_search_params() {
+        let params = SearchParams {
+            query: "axum middleware".into(),
+            site_search: Some("docs.rs".into()),
+            site_search_filter: Some(SiteSearchFilter::Exclude),
+            sort: Some(SortOrder::Date),
+            date_restrict: Some("y1".into()),
+            ..Default::default()
+        };
+        params.validate().unwrap();
+        let pairs = params.query_pairs();
+        assert!(pairs.contains(&("siteSearchFilter", "e".to_owned())));
+        assert!(pairs.contains(&("sort", "date".to_owned())));
+        assert!(pairs.contains(&("dateRestrict", "y1".to_owned())));
+
+        // a filter without a site means nothing to Google
+        let params = SearchParams {
+            site_search_filter: Some(SiteSearchFilter::Include),
+            ..Default::default()
+        };
+        assert!(
+            !params
+                .query_pairs()
+                .iter()
+                .any(|(name, _)| *name == "siteSearchFilter")
+        );
+
+        for invalid in [
+            SearchParams {
+                num: Some(11),
+                ..Default::default()
+            },
+            SearchParams {
+                start: Some(95),
+                num: Some(10),
+                ..Default::default()
+            },
+            SearchParams {
+                date_restrict: Some("1y".into()),
+                ..Default::default()
+            },
+            // too many days to count
+            SearchParams {
+                date_restrict: Some("y99999999999999999".into()),
+                ..Default::default()
+            },
+            SearchParams {
+                date_restrict: Some("d99999999999999999999".into()),
+                ..Default::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_restrict_start() {
+        let now = 1_709_251_200;
+        assert_eq!(restrict_start("w1", now).unwrap(), now - 7 * 24 * 60 * 60);
+        assert_eq!(restrict_start("y100", now).unwrap(), 0);
+        // a count of days that fits, but not as seconds
+        assert!(restrict_start("d99999999999999999", now).is_err());
+        assert!(restrict_start("1y", now).is_err());
+    }
+
+    #[test]
+    fn test_google_error() {
+        let quota = r#"{"error": {"code": 429, "message": "Quota exceeded for quota metric 'Queries'", "errors": [{"message": "Quota exceeded", "domain": "global", "reason": "rateLimitExceeded"}], "status": "RESOURCE_EXHAUSTED"}}"#;
+        let error = google_error(reqwest::StatusCode::TOO_MANY_REQUESTS, quota);
+        assert!(error.message.contains("quota is used up"));
+
+        let bad_key = r#"{"error": {"code": 400, "message": "API key not valid. Please pass a valid API key.", "errors": [{"message": "API key not valid. Please pass a valid API key.", "domain": "global", "reason": "badRequest"}], "status": "INVALID_ARGUMENT"}}"#;
+        let error = google_error(reqwest::StatusCode::BAD_REQUEST, bad_key);
+        assert!(error.message.contains("misconfigured"));
+        assert!(error.message.contains("API key not valid"));
+
+        let error = google_error(reqwest::StatusCode::BAD_GATEWAY, "<html>oops</html>");
+        assert!(error.message.contains("502"));
+    }
+
     #[test]
     fn test_so_question() {
         let mut data_file = File::open("testdata/so-question.json").unwrap();
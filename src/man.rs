@@ -0,0 +1,997 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{Context, anyhow};
+use flate2::read::GzDecoder;
+use url::Url;
+
+use crate::passage::truncate;
+
+/// Pages like bash(1) run to hundreds of kilobytes, so long pages are best read a heading at
+/// a time
+const MAX_MAN_CHARS: usize = 40_000;
+
+/// The order sections are searched in when none is given, as man does
+const SECTION_ORDER: &[&str] = &["1", "8", "3", "2", "5", "4", "9", "6", "7"];
+
+/// How much of each page is read to find its NAME section for apropos
+const NAME_BYTES: u64 = 4096;
+
+/// How long a command gets to print its `--help`
+const HELP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How far list bodies and `.RS` blocks are indented
+const INDENT: usize = 4;
+
+/// A page found by `apropos`
+struct Entry {
+    names: String,
+    section: String,
+    description: String,
+}
+
+/// The man pages installed under the directories of a MANPATH-style list
+pub struct ManPages {
+    dirs: Vec<PathBuf>,
+    /// the file names of the installed pages without `.gz`, e.g. "ls.1", listed once so URLs
+    /// can be matched to them without touching the disk
+    installed: HashSet<String>,
+    /// the NAME line of every page, read the first time apropos is used
+    index: tokio::sync::OnceCell<Arc<Vec<Entry>>>,
+}
+
+impl ManPages {
+    pub fn new(path: &str) -> Self {
+        let dirs: Vec<PathBuf> = path
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        Self {
+            installed: list_pages(&dirs),
+            dirs,
+            index: tokio::sync::OnceCell::new(),
+        }
+    }
+
+    /// Whether the page `name` is installed in exactly `section`, e.g. "3" or "3ssl"
+    pub fn installed(&self, name: &str, section: &str) -> bool {
+        self.installed.contains(&format!("{}.{}", name, section))
+    }
+
+    /// Renders the page `name` from `section` (or the first section that has it, in man's
+    /// order) as plain text, optionally only the part under `heading`, e.g. "OPTIONS"
+    pub async fn page(
+        &self,
+        name: &str,
+        section: Option<&str>,
+        heading: Option<&str>,
+    ) -> Result<String, anyhow::Error> {
+        if name.is_empty() || name.contains('/') || name.starts_with('.') {
+            return Err(anyhow!("invalid man page name {}", name));
+        }
+        let dirs = self.dirs.clone();
+        let name = name.to_owned();
+        let section = section.map(str::to_owned);
+        let (source, dir) =
+            tokio::task::spawn_blocking(move || find_page(&dirs, &name, section.as_deref()))
+                .await??;
+        // pages like lzcat(1) are a `.so` link to another page
+        let source = match source.trim_start().strip_prefix(".so ") {
+            Some(target) => {
+                let target = target.lines().next().unwrap_or("").trim();
+                // only paths within the man directory, e.g. "man1/xz.1"; an absolute one
+                // would replace the directory when joined
+                let within = Path::new(target)
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)));
+                if target.is_empty() || !within {
+                    return Err(anyhow!("invalid .so link to {}", target));
+                }
+                read_page(&dir.join(target), u64::MAX)?
+            }
+            None => source,
+        };
+        let text = render(&source);
+        Ok(match heading {
+            Some(heading) => {
+                let part = heading_section(&text, heading).ok_or_else(|| {
+                    anyhow!(
+                        "the page has no heading {}, it has: {}",
+                        heading,
+                        headings(&text).join(", ")
+                    )
+                })?;
+                truncate(part, MAX_MAN_CHARS)
+            }
+            None if text.chars().count() > MAX_MAN_CHARS => format!(
+                "{}\n\nHeadings, which can be read one at a time: {}",
+                truncate(&text, MAX_MAN_CHARS),
+                headings(&text).join(", ")
+            ),
+            None => text,
+        })
+    }
+
+    /// Searches the NAME lines of all pages for `query`, like apropos: a page matches if its
+    /// names or description contain every word of the query
+    pub async fn apropos(
+        &self,
+        query: &str,
+        section: Option<&str>,
+        num: usize,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let index = self
+            .index
+            .get_or_try_init(|| async {
+                let dirs = self.dirs.clone();
+                Ok::<_, anyhow::Error>(Arc::new(
+                    tokio::task::spawn_blocking(move || index_pages(&dirs)).await?,
+                ))
+            })
+            .await?;
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut matches: Vec<&Entry> = index
+            .iter()
+            .filter(|entry| section.is_none_or(|section| entry.section.starts_with(section)))
+            .filter(|entry| {
+                let text = format!("{} {}", entry.names, entry.description).to_lowercase();
+                words.iter().all(|word| text.contains(word.as_str()))
+            })
+            .collect();
+        // pages named after the query first
+        matches.sort_by_key(|entry| {
+            !entry
+                .names
+                .split(", ")
+                .any(|name| name.eq_ignore_ascii_case(query.trim()))
+        });
+        Ok(matches
+            .into_iter()
+            .take(num)
+            .map(|entry| {
+                format!(
+                    "{} ({}) - {}",
+                    entry.names, entry.section, entry.description
+                )
+            })
+            .collect())
+    }
+}
+
+/// Runs `command --help` from the server's PATH, for commands that have no man page. What it
+/// prints is returned, from stdout or, as some commands print help there, stderr
+pub async fn command_help(command: &str) -> Result<String, anyhow::Error> {
+    let valid = command
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c));
+    if command.is_empty() || !valid || command.starts_with(['-', '.']) {
+        return Err(anyhow!("invalid command name {}", command));
+    }
+    let output = tokio::time::timeout(
+        HELP_TIMEOUT,
+        tokio::process::Command::new(command)
+            .arg("--help")
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| anyhow!("{} --help didn't finish in time", command))?
+    .with_context(|| format!("unable to run {} --help", command))?;
+    let text = match String::from_utf8_lossy(&output.stdout).trim() {
+        "" => String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        stdout => stdout.to_owned(),
+    };
+    if text.is_empty() {
+        return Err(anyhow!("{} --help printed nothing", command));
+    }
+    Ok(truncate(&text, MAX_MAN_CHARS))
+}
+
+/// The page and section of a man7.org URL, e.g.
+/// `https://man7.org/linux/man-pages/man1/ls.1.html`
+pub fn man7_page(url: &Url) -> Option<(String, String)> {
+    let file = url.path().strip_prefix("/linux/man-pages/man")?;
+    let (_, file) = file.split_once('/')?;
+    let (name, section) = file.strip_suffix(".html")?.rsplit_once('.')?;
+    Some((name.to_owned(), section.to_owned()))
+}
+
+/// The file names of the pages in the section directories of `dirs`, without `.gz`
+fn list_pages(dirs: &[PathBuf]) -> HashSet<String> {
+    dirs.iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|sections| sections.flatten())
+        .filter(|section| {
+            section
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("man"))
+        })
+        .filter_map(|section| fs::read_dir(section.path()).ok())
+        .flat_map(|files| files.flatten())
+        .filter_map(|file| {
+            let file_name = file.file_name().into_string().ok()?;
+            Some(match file_name.strip_suffix(".gz") {
+                Some(stem) => stem.to_owned(),
+                None => file_name,
+            })
+        })
+        .collect()
+}
+
+/// Finds and reads a page, returning it with the man directory it's in
+fn find_page(
+    dirs: &[PathBuf],
+    name: &str,
+    section: Option<&str>,
+) -> Result<(String, PathBuf), anyhow::Error> {
+    let sections: Vec<&str> = match section {
+        Some(section) => vec![section],
+        None => SECTION_ORDER.to_vec(),
+    };
+    for section in sections.iter() {
+        // section 3 has pages in man3 named e.g. "printf.3" or "SSL_read.3ssl"
+        let Some(first) = section.chars().next().filter(char::is_ascii_alphanumeric) else {
+            return Err(anyhow!("invalid man page section {}", section));
+        };
+        let directory = format!("man{}", first);
+        for dir in dirs {
+            let Ok(files) = fs::read_dir(dir.join(&directory)) else {
+                continue;
+            };
+            for file in files.flatten() {
+                let file_name = file.file_name();
+                let Some(file_name) = file_name.to_str() else {
+                    continue;
+                };
+                let file_name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+                if let Some((page, page_section)) = file_name.rsplit_once('.')
+                    && page == name
+                    && page_section.starts_with(section)
+                {
+                    return Ok((read_page(&file.path(), u64::MAX)?, dir.clone()));
+                }
+            }
+        }
+    }
+    Err(match section {
+        Some(section) => anyhow!("no man page for {} in section {}", name, section),
+        None => anyhow!("no man page for {}", name),
+    })
+}
+
+/// Reads up to `limit` bytes of a page, decompressing it if it's gzipped as most
+/// distributions ship them
+fn read_page(path: &Path, limit: u64) -> Result<String, anyhow::Error> {
+    let path = if path.exists() {
+        path.to_path_buf()
+    } else {
+        // `.so` links name the uncompressed file
+        let mut gz = path.as_os_str().to_owned();
+        gz.push(".gz");
+        PathBuf::from(gz)
+    };
+    let file = fs::File::open(&path)
+        .with_context(|| format!("unable to read man page {}", path.display()))?;
+    let mut text = Vec::new();
+    if path.extension().is_some_and(|extension| extension == "gz") {
+        GzDecoder::new(file).take(limit).read_to_end(&mut text)
+    } else {
+        file.take(limit).read_to_end(&mut text)
+    }
+    .with_context(|| format!("unable to read man page {}", path.display()))?;
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+fn index_pages(dirs: &[PathBuf]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for dir in dirs {
+        let Ok(sections) = fs::read_dir(dir) else {
+            continue;
+        };
+        for section in sections.flatten() {
+            let is_section = section
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("man"));
+            if !is_section {
+                continue;
+            }
+            let Ok(files) = fs::read_dir(section.path()).map(|files| files.flatten()) else {
+                continue;
+            };
+            for file in files {
+                // links are alternative names of pages, which their NAME lines list
+                if file
+                    .file_type()
+                    .is_ok_and(|file_type| file_type.is_symlink())
+                {
+                    continue;
+                }
+                let path = file.path();
+                let file_name = file.file_name();
+                let Some(file_name) = file_name.to_str() else {
+                    continue;
+                };
+                let file_name = file_name.strip_suffix(".gz").unwrap_or(file_name);
+                let Some((page, page_section)) = file_name.rsplit_once('.') else {
+                    continue;
+                };
+                // the NAME section is at the top of the page
+                let Ok(source) = read_page(&path, NAME_BYTES) else {
+                    continue;
+                };
+                // links to other pages are found under the page they link to
+                if source.trim_start().starts_with(".so ") {
+                    continue;
+                }
+                let (names, description) =
+                    name_line(&source).unwrap_or_else(|| (page.to_owned(), String::new()));
+                entries.push(Entry {
+                    names,
+                    section: page_section.to_owned(),
+                    description,
+                });
+            }
+        }
+    }
+    entries.sort_by(|a, b| (&a.section, &a.names).cmp(&(&b.section, &b.names)));
+    // the same page can be installed in several directories of the path
+    entries.dedup_by(|a, b| a.section == b.section && a.names == b.names);
+    entries
+}
+
+/// The names and description from a page's NAME section, e.g. ("gzip, gunzip, zcat",
+/// "compress or expand files")
+fn name_line(source: &str) -> Option<(String, String)> {
+    // only the start of the page is needed, which saves rendering all of it
+    let end = source
+        .match_indices("\n.S")
+        .map(|(index, _)| index)
+        .nth(1)
+        .unwrap_or(source.len());
+    let text = render(&source[..end]);
+    let name = heading_section(&text, "NAME")?;
+    let line = name
+        .lines()
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let (names, description) = line.split_once(" - ").or_else(|| line.split_once(" — "))?;
+    Some((names.trim().to_owned(), description.trim().to_owned()))
+}
+
+/// The part of a rendered page under `heading`, up to the next heading of the same level
+fn heading_section<'a>(text: &'a str, heading: &str) -> Option<&'a str> {
+    let start = text.match_indices("## ").find_map(|(index, _)| {
+        let at_line_start = index == 0 || text[..index].ends_with('\n');
+        let line = text[index + 3..].lines().next()?;
+        (at_line_start && line.trim().eq_ignore_ascii_case(heading.trim())).then_some(index)
+    })?;
+    let end = text[start + 3..]
+        .match_indices("\n## ")
+        .next()
+        .map_or(text.len(), |(index, _)| start + 3 + index);
+    Some(text[start..end].trim_end())
+}
+
+fn headings(text: &str) -> Vec<&str> {
+    text.lines()
+        .filter_map(|line| line.strip_prefix("## "))
+        .collect()
+}
+
+/// Renders man(7) and mdoc(7) pages to plain text, with section headings as `##` so they
+/// can be picked out. Only the macros and escapes that matter for reading are handled; the
+/// rest, e.g. spacing and font changes, are dropped
+pub fn render(source: &str) -> String {
+    let mut out = Output::default();
+    let mut lines = source.lines();
+    // `.TP` puts the next line on its own as the tag of the paragraph after it
+    let mut tag_next = false;
+    while let Some(line) = lines.next() {
+        let Some(request) = line.strip_prefix('.').or_else(|| line.strip_prefix('\'')) else {
+            let text = unescape(line);
+            if tag_next {
+                tag_next = false;
+                out.tag(&text);
+            } else {
+                out.text(&text);
+            }
+            continue;
+        };
+        let request = request.trim_start();
+        let (name, rest) = request
+            .split_once(char::is_whitespace)
+            .unwrap_or((request, ""));
+        let args = arguments(rest);
+        let joined = |separator: &str| {
+            args.iter()
+                .map(|arg| unescape(arg))
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+        match name {
+            "\\\"" | "" => {}
+            // definitions and ignored blocks run until a line of ".."
+            "de" | "de1" | "ig" | "am" => {
+                for line in lines.by_ref() {
+                    if line.trim() == ".." {
+                        break;
+                    }
+                }
+            }
+            "TH" | "Dt" => {
+                let title = args.first().map_or(String::new(), |title| unescape(title));
+                let section = args
+                    .get(1)
+                    .map_or(String::new(), |section| unescape(section));
+                out.heading(&format!("# {}({})", title, section));
+            }
+            "SH" | "Sh" | "SS" | "Ss" => {
+                let level = if name.starts_with("SH") || name == "Sh" {
+                    "##"
+                } else {
+                    "###"
+                };
+                let text = if args.is_empty() {
+                    lines.next().map(unescape).unwrap_or_default()
+                } else {
+                    joined(" ")
+                };
+                out.indent = 0;
+                out.heading(&format!("{} {}", level, text));
+            }
+            "PP" | "P" | "LP" | "Pp" | "sp" | "HP" => {
+                out.flush();
+                out.body_indent = out.indent;
+            }
+            "br" => out.line_break(),
+            "TP" | "TQ" => {
+                out.flush();
+                tag_next = true;
+            }
+            "IP" => {
+                out.flush();
+                match args.first() {
+                    Some(tag) if !tag.is_empty() => out.tag(&unescape(tag)),
+                    _ => out.body_indent = out.indent + INDENT,
+                }
+            }
+            "RS" => {
+                out.flush();
+                out.indent += INDENT;
+                out.body_indent = out.indent;
+            }
+            "RE" => {
+                out.flush();
+                out.indent = out.indent.saturating_sub(INDENT);
+                out.body_indent = out.indent;
+            }
+            "nf" | "EX" => {
+                out.flush();
+                out.verbatim = true;
+            }
+            "fi" | "EE" => {
+                out.flush();
+                out.verbatim = false;
+            }
+            // fonts apply to their arguments, or to the next line when there are none
+            "B" | "I" | "SM" | "SB" | "SY" | "BR" | "BI" | "IB" | "IR" | "RB" | "RI"
+                if !args.is_empty() =>
+            {
+                // the alternating fonts join their arguments without spaces
+                let alternating = matches!(name, "BR" | "BI" | "IB" | "IR" | "RB" | "RI");
+                let text = joined(if alternating { "" } else { " " });
+                if tag_next {
+                    tag_next = false;
+                    out.tag(&text);
+                } else {
+                    out.text(&text);
+                }
+            }
+            "OP" => out.text(&format!("[{}]", joined(" "))),
+            "UR" | "MT" => out.text(&joined(" ")),
+            "TS" => {
+                out.flush();
+                table(&mut lines, &mut out);
+            }
+            // mdoc lists: each item is tagged, or bulleted
+            "Bl" => {
+                out.flush();
+                out.lists
+                    .push(args.iter().any(|arg| *arg == "-bullet" || *arg == "-dash"));
+            }
+            "El" => {
+                out.flush();
+                out.lists.pop();
+                out.indent = INDENT * out.lists.len();
+                out.body_indent = out.indent;
+            }
+            "It" => {
+                out.flush();
+                out.indent = INDENT * out.lists.len().saturating_sub(1);
+                let tag = if out.lists.last() == Some(&true) {
+                    "•".to_owned()
+                } else {
+                    mdoc(&args)
+                };
+                out.tag(&tag);
+            }
+            "Bd" => {
+                out.flush();
+                out.verbatim = args.contains(&"-literal") || args.contains(&"-unfilled");
+                out.indent += INDENT;
+                out.body_indent = out.indent;
+            }
+            "Ed" => {
+                out.flush();
+                out.verbatim = false;
+                out.indent = out.indent.saturating_sub(INDENT);
+                out.body_indent = out.indent;
+            }
+            "D1" | "Dl" => {
+                out.flush();
+                out.body_indent = out.indent + INDENT;
+                out.text(&mdoc(&args));
+                out.flush();
+                out.body_indent = out.indent;
+            }
+            "Nd" => out.text(&format!("- {}", mdoc(&args))),
+            "Nm" if args.is_empty() => {
+                out.text(&out.name.clone());
+            }
+            "Nm" => {
+                if out.name.is_empty() {
+                    out.name = unescape(args[0]);
+                }
+                out.text(&mdoc(&args));
+            }
+            _ if MDOC_MACROS.contains(&name) => {
+                let mut tokens = vec![name];
+                tokens.extend(args);
+                out.text(&mdoc(&tokens));
+            }
+            // spacing, hyphenation, registers, conditionals and the like
+            _ => {}
+        }
+    }
+    out.finish()
+}
+
+/// Accumulates rendered text, filling paragraphs and indenting lists
+#[derive(Default)]
+struct Output {
+    blocks: Vec<String>,
+    /// the paragraph being filled
+    line: String,
+    indent: usize,
+    /// the indent of the current paragraph, deeper than `indent` under a tag
+    body_indent: usize,
+    verbatim: bool,
+    /// the open mdoc lists, true for bulleted ones
+    lists: Vec<bool>,
+    /// the page's name, as mdoc's `.Nm` repeats it
+    name: String,
+}
+
+impl Output {
+    fn text(&mut self, text: &str) {
+        if self.verbatim {
+            self.blocks
+                .push(format!("{}{}", " ".repeat(self.body_indent), text));
+            return;
+        }
+        if text.trim().is_empty() {
+            self.flush();
+            return;
+        }
+        // punctuation after e.g. `.BR` ends the sentence rather than starting a word
+        let punctuation = text.starts_with([',', '.', ';', ':', ')'])
+            && (text.len() == 1 || text[1..].starts_with(char::is_whitespace));
+        if !self.line.is_empty() && !punctuation {
+            self.line.push(' ');
+        }
+        self.line.push_str(text.trim());
+    }
+
+    fn tag(&mut self, tag: &str) {
+        self.flush();
+        self.blocks
+            .push(format!("{}{}", " ".repeat(self.indent), tag));
+        self.body_indent = self.indent + INDENT;
+    }
+
+    fn heading(&mut self, heading: &str) {
+        self.flush();
+        self.blocks.push(String::new());
+        self.blocks.push(heading.to_owned());
+        self.blocks.push(String::new());
+        self.body_indent = self.indent;
+    }
+
+    fn line_break(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.blocks
+                .push(format!("{}{}", " ".repeat(self.body_indent), line));
+        }
+    }
+
+    fn flush(&mut self) {
+        self.line_break();
+        if self.blocks.last().is_some_and(|last| !last.is_empty()) {
+            self.blocks.push(String::new());
+        }
+    }
+
+    fn finish(mut self) -> String {
+        self.flush();
+        let mut text = String::new();
+        let mut blank = true;
+        for block in self.blocks {
+            // collapse runs of blank lines
+            if block.trim().is_empty() {
+                if !blank {
+                    text.push('\n');
+                }
+                blank = true;
+                continue;
+            }
+            blank = false;
+            text.push_str(block.trim_end());
+            text.push('\n');
+        }
+        text.trim().to_owned()
+    }
+}
+
+/// Renders a tbl(1) table as its cells separated by two spaces, skipping its format lines
+fn table<'a>(lines: &mut impl Iterator<Item = &'a str>, out: &mut Output) {
+    let mut in_format = true;
+    for line in lines.by_ref() {
+        if line.starts_with(".TE") {
+            break;
+        }
+        if in_format {
+            // the format ends with a line ending in "."
+            in_format = !line.trim_end().ends_with('.');
+            continue;
+        }
+        if line.starts_with('.') || line.trim() == "_" || line.trim() == "=" {
+            continue;
+        }
+        let row: Vec<String> = line
+            .split('\t')
+            .map(|cell| unescape(cell.trim_start_matches("T{").trim_end_matches("T}")))
+            .collect();
+        out.blocks
+            .push(format!("{}{}", " ".repeat(out.body_indent), row.join("  ")));
+    }
+    out.blocks.push(String::new());
+}
+
+/// mdoc macros that can appear inline on a line of other macros
+const MDOC_MACROS: &[&str] = &[
+    "Ad", "An", "Ao", "Ac", "Ap", "Aq", "Ar", "Bo", "Bc", "Bq", "Brq", "Cd", "Cm", "Dq", "Dv",
+    "Em", "Er", "Ev", "Fa", "Fl", "Fn", "Fo", "Fc", "Ft", "Ic", "In", "Li", "Lk", "Ms", "Mt", "Ns",
+    "Oo", "Oc", "Op", "Pa", "Pf", "Pq", "Ql", "Qq", "Sq", "Sx", "Sy", "Tn", "Va", "Vt", "Xr", "St",
+    "Ux", "Bx", "Ox", "Fx", "Nx", "Dx", "At", "Lb", "Rv", "Ex",
+];
+
+/// Renders a line of mdoc macros and words, e.g. `Fl o Ar file` as "-o file"
+fn mdoc(tokens: &[&str]) -> String {
+    let mut text = String::new();
+    let mut no_space = false;
+    // characters closing a quote or an optional part, opened when their macro was seen
+    let mut closers: Vec<&str> = Vec::new();
+    // the last macro, and how many words followed it
+    let mut current = "";
+    let mut words = 0;
+    for token in tokens {
+        let token = *token;
+        if MDOC_MACROS.contains(&token) || token == "Nm" || token == "It" {
+            current = token;
+            words = 0;
+            let opener = match token {
+                "Op" | "Oo" => Some(("[", "]")),
+                "Dq" | "Qq" => Some(("\"", "\"")),
+                "Sq" | "Ql" => Some(("'", "'")),
+                "Pq" => Some(("(", ")")),
+                "Aq" | "Ao" => Some(("<", ">")),
+                "Bq" | "Bo" => Some(("[", "]")),
+                "Brq" => Some(("{", "}")),
+                _ => None,
+            };
+            if let Some((open, close)) = opener {
+                if !text.is_empty() && !no_space {
+                    text.push(' ');
+                }
+                text.push_str(open);
+                no_space = true;
+                // the explicit blocks are closed by their own macro
+                if !matches!(token, "Oo" | "Ao" | "Bo") {
+                    closers.push(close);
+                }
+            }
+            match token {
+                "Oc" | "Bc" => text.push(']'),
+                "Ac" => text.push('>'),
+                "Ns" | "Pf" => no_space = true,
+                "Ap" => {
+                    text.push('\'');
+                    no_space = true;
+                }
+                "Fl" => {
+                    if !text.is_empty() && !no_space {
+                        text.push(' ');
+                    }
+                    text.push('-');
+                    no_space = true;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        let word = unescape(token);
+        let punctuation = word.len() == 1 && ".,;:)]?!".contains(word.as_str());
+        if current == "Xr" && words == 1 && !punctuation {
+            // the section of an `Xr name section` reference
+            text.push_str(&format!("({})", word));
+        } else {
+            if !text.is_empty() && !no_space && !punctuation {
+                text.push(' ');
+            }
+            text.push_str(&word);
+        }
+        no_space = false;
+        words += 1;
+    }
+    for close in closers.iter().rev() {
+        text.push_str(close);
+    }
+    text
+}
+
+/// Splits a macro's arguments, which may be double quoted
+fn arguments(text: &str) -> Vec<&str> {
+    let mut args = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            args.push(&quoted[..end]);
+            rest = quoted.get(end + 1..).unwrap_or("").trim_start();
+        } else {
+            // a comment ends the arguments
+            if rest.starts_with("\\\"") {
+                break;
+            }
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            args.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+    }
+    args
+}
+
+/// Named characters, as in `\(em` or `\[em]`, and the strings of `\*(lq`
+fn special(name: &str) -> &'static str {
+    match name {
+        "em" => "—",
+        "en" => "–",
+        "hy" | "mi" | "-" => "-",
+        "rs" => "\\",
+        "aq" | "oq" | "cq" | "Aq" => "'",
+        "dq" | "lq" | "rq" | "Lq" | "Rq" | "Q" | "q" => "\"",
+        "bu" => "•",
+        "co" => "©",
+        "rg" | "R" => "®",
+        "tm" | "Tm" => "™",
+        "ti" | "ap" => "~",
+        "ha" | "a^" => "^",
+        "pl" => "+",
+        "mu" => "×",
+        "di" => "÷",
+        ">=" => "≥",
+        "<=" => "≤",
+        "!=" => "≠",
+        "->" => "→",
+        "<-" => "←",
+        "de" => "°",
+        "ga" => "`",
+        "ul" | "ru" => "_",
+        "ba" | "br" => "|",
+        "sl" => "/",
+        "lB" => "[",
+        "rB" => "]",
+        "lC" => "{",
+        "rC" => "}",
+        "la" | "fo" => "<",
+        "ra" | "fc" => ">",
+        "sq" => "□",
+        "ss" => "ß",
+        _ => "",
+    }
+}
+
+/// Replaces groff escapes with the text they stand for and drops font and size changes
+fn unescape(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    // reads an escape's name: one character, two after "(", or up to "]" after "["
+    fn name(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+        match chars.next() {
+            Some('(') => chars.by_ref().take(2).collect(),
+            Some('[') => chars.by_ref().take_while(|c| *c != ']').collect(),
+            Some(c) => c.to_string(),
+            None => String::new(),
+        }
+    }
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            // the rest of the line is a comment
+            Some('"') | Some('#') => break,
+            Some('-') => text.push('-'),
+            Some('e') | Some('\\') => text.push('\\'),
+            Some('.') => text.push('.'),
+            Some('\'') => text.push('\''),
+            Some('`') => text.push('`'),
+            Some(' ') | Some('0') | Some('~') => text.push(' '),
+            Some('t') => text.push('\t'),
+            Some('(') => {
+                let special_name: String = chars.by_ref().take(2).collect();
+                text.push_str(special(&special_name));
+            }
+            Some('[') => {
+                let special_name: String = chars.by_ref().take_while(|c| *c != ']').collect();
+                text.push_str(special(&special_name));
+            }
+            Some('*') => text.push_str(special(&name(&mut chars))),
+            // fonts and registers
+            Some('f') | Some('F') | Some('n') | Some('g') | Some('k') | Some('m') | Some('M') => {
+                name(&mut chars);
+            }
+            Some('s') => {
+                if matches!(chars.peek(), Some('+') | Some('-')) {
+                    chars.next();
+                }
+                match chars.peek() {
+                    Some('(') | Some('[') => {
+                        name(&mut chars);
+                    }
+                    _ => {
+                        chars.next();
+                    }
+                }
+            }
+            // escapes with a quoted argument, e.g. `\h'1m'`
+            Some('h') | Some('v') | Some('w') | Some('o') | Some('l') | Some('L') | Some('D')
+            | Some('X') | Some('Z') | Some('A') | Some('b') | Some('x') | Some('R') => {
+                if let Some(delimiter) = chars.next() {
+                    for c in chars.by_ref() {
+                        if c == delimiter {
+                            break;
+                        }
+                    }
+                }
+            }
+            // \& \| \^ \c \% \: \) \/ \, and anything else print nothing
+            _ => {}
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let page = r#".\" a comment
+.TH FROB 1 "May 2025" "frob 2.1" "User Commands"
+.SH NAME
+frob, unfrob \- frobnicate files
+.SH SYNOPSIS
+.B frob
+[\fI\,OPTION\/\fR]... \fIFILE\fR...
+.SH OPTIONS
+.TP
+\fB\-n\fR, \fB\-\-dry\-run\fR
+show what would be done,
+without doing it
+.TP
+.BR \-o " " \fIfile\fR
+write to \fIfile\fR \(em not \*(lqstdout\*(rq
+.SH EXAMPLES
+.nf
+frob \-n a.txt
+  b.txt
+.fi
+.SH SEE ALSO
+.BR unfrob (1)
+"#;
+        let text = render(page);
+        assert!(text.starts_with("# FROB(1)\n\n## NAME\n\nfrob, unfrob - frobnicate files"));
+        assert!(text.contains("frob [OPTION]... FILE..."));
+        assert!(text.contains(
+            "-n, --dry-run\n    show what would be done, without doing it\n\n-o file\n    write to file — not \"stdout\""
+        ));
+        assert!(text.contains("frob -n a.txt\n  b.txt"));
+        assert!(text.contains("unfrob(1)"));
+        assert_eq!(
+            heading_section(&text, "examples"),
+            Some("## EXAMPLES\n\nfrob -n a.txt\n  b.txt")
+        );
+        assert_eq!(
+            name_line(page),
+            Some(("frob, unfrob".into(), "frobnicate files".into()))
+        );
+
+        let mdoc_page = ".Dd May 1, 2025\n.Dt TAR 1\n.Sh NAME\n.Nm tar\n.Nd manipulate tape archives\n\
+            .Sh SYNOPSIS\n.Nm\n.Op Fl v\n.Fl f Ar file\n.Sh OPTIONS\n.Bl -tag -width Ds\n\
+            .It Fl C Ar directory\nChange to\n.Ar directory .\n.El\n.Sh SEE ALSO\n.Xr gzip 1 ,\n.Xr cpio 5\n";
+        let text = render(mdoc_page);
+        assert!(text.contains("## NAME\n\ntar - manipulate tape archives"));
+        assert!(text.contains("tar [-v] -f file"));
+        assert!(text.contains("-C directory\n    Change to directory."));
+        assert!(text.contains("gzip(1), cpio(5)"));
+        assert_eq!(
+            name_line(mdoc_page),
+            Some(("tar".into(), "manipulate tape archives".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_man_pages() {
+        let man = ManPages::new("testdata/man");
+        let page = man.page("frob", None, Some("options")).await.unwrap();
+        assert!(page.starts_with("## OPTIONS\n\n-n, --dry-run"));
+        // section 3 is only searched when asked for, or when there's no section 1 page
+        let page = man.page("frob", Some("3"), None).await.unwrap();
+        assert!(page.starts_with("# FROB(3)"));
+        // unfrob.1 is a link to frob.1
+        let page = man.page("unfrob", None, None).await.unwrap();
+        assert!(page.starts_with("# FROB(1)"));
+        assert!(man.page("../frob", None, None).await.is_err());
+        // escape.1 links to an absolute path outside the man directory
+        assert!(man.page("escape", None, None).await.is_err());
+
+        let results = man.apropos("frobnicate", None, 10).await.unwrap();
+        assert_eq!(
+            results,
+            [
+                "frob, unfrob (1) - frobnicate files",
+                "frob (3) - frobnicate a buffer"
+            ]
+        );
+        assert_eq!(man.apropos("buffer", Some("1"), 10).await.unwrap().len(), 0);
+        assert_eq!(
+            man7_page(&Url::parse("https://man7.org/linux/man-pages/man3/printf.3.html").unwrap()),
+            Some(("printf".into(), "3".into()))
+        );
+        assert!(man.installed("frob", "1") && man.installed("frob", "3"));
+        // cargo is on the PATH wherever the tests run
+        let help = command_help("cargo").await.unwrap();
+        assert!(help.contains("Usage: cargo"));
+        assert!(command_help("../cargo").await.is_err());
+        assert!(command_help("-rf").await.is_err());
+        assert!(!man.installed("frob", "8") && !man.installed("printf", "3"));
+    }
+}
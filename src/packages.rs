@@ -0,0 +1,1026 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+use anyhow::{Context, anyhow};
+use futures::StreamExt;
+use reqwest::StatusCode;
+use rmcp::schemars::JsonSchema;
+use serde::{Deserialize, de::DeserializeOwned};
+use url::Url;
+
+use crate::{
+    http::HttpClient,
+    passage::{strip_tags, truncate},
+    search::civil_from_days,
+};
+
+/// How many of the most recent versions are listed
+const RECENT_VERSIONS: usize = 10;
+
+/// READMEs of popular packages run long, and the start usually says what's needed
+const MAX_README_CHARS: usize = 20_000;
+
+/// How many of the recent Go versions' dates are fetched at once. The whole lookup holds a
+/// single `HostLimiter` slot for the proxy, so this keeps the fan-out within polite bounds
+const GO_INFO_CONCURRENCY: usize = 4;
+
+const GO_PROXY: &str = "https://proxy.golang.org";
+const MAVEN_REPO: &str = "https://repo1.maven.org/maven2";
+
+/// The package registries `package_info` can look packages up in
+#[derive(Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Registry {
+    /// npm, for JavaScript and TypeScript
+    Npm,
+    /// the Python Package Index
+    Pypi,
+    /// Go modules, through the Go module proxy
+    Go,
+    /// Maven Central, for Java, Kotlin and Scala. Names are "group:artifact"
+    Maven,
+}
+
+impl Registry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Registry::Npm => "npm",
+            Registry::Pypi => "PyPI",
+            Registry::Go => "Go",
+            Registry::Maven => "Maven Central",
+        }
+    }
+
+    /// The host most of a package's metadata is fetched from
+    pub fn host(&self) -> &'static str {
+        match self {
+            Registry::Npm => "registry.npmjs.org",
+            Registry::Pypi => "pypi.org",
+            Registry::Go => "proxy.golang.org",
+            Registry::Maven => "repo1.maven.org",
+        }
+    }
+}
+
+/// A package version as the registries describe it, reduced to what they have in common
+struct Package {
+    registry: Registry,
+    name: String,
+    version: String,
+    latest: String,
+    description: Option<String>,
+    deprecated: Option<String>,
+    license: Option<String>,
+    repository: Option<String>,
+    homepage: Option<String>,
+    /// registry specific details, e.g. the supported Python versions
+    notes: Vec<String>,
+    dependencies: Vec<String>,
+    recent: Vec<Release>,
+    readme: Option<String>,
+}
+
+struct Release {
+    version: String,
+    date: Option<String>,
+    /// e.g. "yanked" or "deprecated"
+    note: Option<&'static str>,
+}
+
+/// The registry, package name and version of a package page, e.g.
+/// `https://www.npmjs.com/package/@types/node/v/22.0.0` or `https://pypi.org/project/requests/`
+pub fn package_url(url: &Url) -> Option<(Registry, String, Option<String>)> {
+    let segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    match url.host_str()? {
+        "www.npmjs.com" | "npmjs.com" => {
+            let rest = segments.strip_prefix(&["package"])?;
+            let (name, rest) = match rest {
+                [scope, name, rest @ ..] if scope.starts_with('@') => {
+                    (format!("{}/{}", scope, name), rest)
+                }
+                [name, rest @ ..] => (name.to_string(), rest),
+                [] => return None,
+            };
+            let version = match rest {
+                ["v", version, ..] => Some(version.to_string()),
+                _ => None,
+            };
+            Some((Registry::Npm, name, version))
+        }
+        "pypi.org" => match segments.as_slice() {
+            ["project", name, rest @ ..] => Some((
+                Registry::Pypi,
+                name.to_string(),
+                rest.first().map(|version| version.to_string()),
+            )),
+            _ => None,
+        },
+        // the standard library ("net/http") isn't a module, so paths must start with a domain
+        "pkg.go.dev" if segments.first()?.contains('.') => {
+            let path = segments.join("/");
+            match path.split_once('@') {
+                Some((module, rest)) => {
+                    let version = rest.split('/').next().unwrap_or(rest);
+                    Some((Registry::Go, module.to_owned(), Some(version.to_owned())))
+                }
+                None => Some((Registry::Go, path, None)),
+            }
+        }
+        "central.sonatype.com" | "search.maven.org" | "mvnrepository.com" => {
+            match segments.as_slice() {
+                ["artifact", group, artifact, rest @ ..] => Some((
+                    Registry::Maven,
+                    format!("{}:{}", group, artifact),
+                    rest.first().map(|version| version.to_string()),
+                )),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Looks `name` up in `registry`, describing `version` (or the latest version) in Markdown
+pub async fn fetch_package(
+    http: &HttpClient,
+    registry: Registry,
+    name: &str,
+    version: Option<&str>,
+) -> Result<String, anyhow::Error> {
+    let package = match registry {
+        Registry::Npm => fetch_npm(http, name, version).await?,
+        Registry::Pypi => fetch_pypi(http, name, version).await?,
+        Registry::Go => fetch_go(http, name, version).await?,
+        Registry::Maven => fetch_maven(http, name, version).await?,
+    };
+    Ok(render(package))
+}
+
+/// Sends a GET request, turning a 404 into an error saying what wasn't found
+async fn get(
+    http: &HttpClient,
+    url: &str,
+    what: impl FnOnce() -> String,
+) -> Result<reqwest::Response, anyhow::Error> {
+    let res = http
+        .send(http.get(url))
+        .await
+        .with_context(|| format!("fetching {}", url))?;
+    if matches!(res.status(), StatusCode::NOT_FOUND | StatusCode::GONE) {
+        return Err(anyhow!("{} not found", what()));
+    }
+    Ok(res.error_for_status()?)
+}
+
+async fn get_json<T: DeserializeOwned>(
+    http: &HttpClient,
+    url: &str,
+    what: impl FnOnce() -> String,
+) -> Result<T, anyhow::Error> {
+    Ok(get(http, url, what).await?.json().await?)
+}
+
+#[derive(Deserialize)]
+struct NpmPackage {
+    name: String,
+    #[serde(rename = "dist-tags", default)]
+    dist_tags: HashMap<String, String>,
+    /// publish dates by version, plus "created" and "modified"
+    #[serde(default)]
+    time: HashMap<String, String>,
+    #[serde(default)]
+    versions: HashMap<String, NpmVersion>,
+    readme: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NpmVersion {
+    description: Option<String>,
+    /// usually an SPDX expression, but old packages have `{"type": "MIT"}`
+    license: Option<serde_json::Value>,
+    deprecated: Option<String>,
+    repository: Option<NpmRepository>,
+    homepage: Option<String>,
+    #[serde(default)]
+    dependencies: BTreeMap<String, String>,
+    #[serde(rename = "peerDependencies", default)]
+    peer_dependencies: BTreeMap<String, String>,
+    engines: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NpmRepository {
+    Url(String),
+    Object { url: String },
+}
+
+async fn fetch_npm(
+    http: &HttpClient,
+    name: &str,
+    version: Option<&str>,
+) -> Result<Package, anyhow::Error> {
+    // scoped packages are requested as @scope%2Fname
+    let response: NpmPackage = get_json(
+        http,
+        &format!(
+            "https://registry.npmjs.org/{}",
+            name.replacen('/', "%2F", 1)
+        ),
+        || format!("npm package {}", name),
+    )
+    .await?;
+    npm_package(response, version)
+}
+
+fn npm_package(mut response: NpmPackage, version: Option<&str>) -> Result<Package, anyhow::Error> {
+    let latest = response
+        .dist_tags
+        .remove("latest")
+        .ok_or_else(|| anyhow!("{} has no latest version", response.name))?;
+    let wanted = version.unwrap_or(&latest).to_owned();
+    let mut recent: Vec<(String, String)> = response
+        .time
+        .iter()
+        .filter(|(version, _)| response.versions.contains_key(*version))
+        .map(|(version, date)| (version.clone(), date.clone()))
+        .collect();
+    recent.sort_by(|a, b| b.1.cmp(&a.1));
+    let recent = recent
+        .into_iter()
+        .take(RECENT_VERSIONS)
+        .map(|(version, date)| Release {
+            note: response.versions[&version]
+                .deprecated
+                .as_ref()
+                .map(|_| "deprecated"),
+            version,
+            date: Some(date),
+        })
+        .collect();
+    let selected = response
+        .versions
+        .remove(&wanted)
+        .ok_or_else(|| anyhow!("{} has no version {}", response.name, wanted))?;
+    let mut notes = Vec::new();
+    if let Some(released) = response.time.get(&wanted) {
+        notes.push(format!("Released {}", released));
+    }
+    if let Some(engines) = selected.engines.filter(|engines| !engines.is_empty()) {
+        let engines: Vec<_> = engines
+            .iter()
+            .map(|(engine, range)| format!("{} {}", engine, range))
+            .collect();
+        notes.push(format!("Engines: {}", engines.join(", ")));
+    }
+    let mut dependencies: Vec<String> = selected
+        .dependencies
+        .iter()
+        .map(|(name, range)| format!("{} {}", name, range))
+        .collect();
+    dependencies.extend(
+        selected
+            .peer_dependencies
+            .iter()
+            .map(|(name, range)| format!("{} {} (peer)", name, range)),
+    );
+    Ok(Package {
+        registry: Registry::Npm,
+        name: response.name,
+        version: wanted,
+        latest,
+        description: selected.description,
+        deprecated: selected.deprecated,
+        license: selected.license.map(|license| match license {
+            serde_json::Value::String(license) => license,
+            license => license["type"].as_str().unwrap_or("unknown").to_owned(),
+        }),
+        repository: selected.repository.map(|repository| match repository {
+            NpmRepository::Url(url) | NpmRepository::Object { url } => url,
+        }),
+        homepage: selected.homepage,
+        notes,
+        dependencies,
+        recent,
+        readme: response.readme,
+    })
+}
+
+#[derive(Deserialize)]
+struct PyPiResponse {
+    info: PyPiInfo,
+    /// the files of each version. Only included when no version is requested
+    #[serde(default)]
+    releases: BTreeMap<String, Vec<PyPiFile>>,
+}
+
+#[derive(Deserialize)]
+struct PyPiInfo {
+    name: String,
+    version: String,
+    summary: Option<String>,
+    license: Option<String>,
+    license_expression: Option<String>,
+    home_page: Option<String>,
+    project_urls: Option<BTreeMap<String, String>>,
+    requires_dist: Option<Vec<String>>,
+    requires_python: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    yanked: bool,
+    yanked_reason: Option<String>,
+    #[serde(default)]
+    classifiers: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PyPiFile {
+    upload_time_iso_8601: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+async fn fetch_pypi(
+    http: &HttpClient,
+    name: &str,
+    version: Option<&str>,
+) -> Result<Package, anyhow::Error> {
+    let what = || format!("PyPI project {}", name);
+    let project: PyPiResponse =
+        get_json(http, &format!("https://pypi.org/pypi/{}/json", name), what).await?;
+    let selected = match version.filter(|version| *version != project.info.version) {
+        Some(version) => Some(
+            get_json::<PyPiResponse>(
+                http,
+                &format!("https://pypi.org/pypi/{}/{}/json", name, version),
+                || format!("version {} of PyPI project {}", version, name),
+            )
+            .await?
+            .info,
+        ),
+        None => None,
+    };
+    Ok(pypi_package(project, selected))
+}
+
+/// Describes `selected`, or the latest version of `project` if none is given
+fn pypi_package(project: PyPiResponse, selected: Option<PyPiInfo>) -> Package {
+    let latest = project.info.version.clone();
+    let mut recent: Vec<Release> = project
+        .releases
+        .iter()
+        .filter_map(|(version, files)| {
+            // versions without files can't be installed
+            let first = files.iter().map(|file| &file.upload_time_iso_8601).min()?;
+            Some(Release {
+                version: version.clone(),
+                date: Some(first.clone()),
+                note: files.iter().all(|file| file.yanked).then_some("yanked"),
+            })
+        })
+        .collect();
+    recent.sort_by(|a, b| b.date.cmp(&a.date));
+    recent.truncate(RECENT_VERSIONS);
+    let released = project
+        .releases
+        .get(
+            &selected
+                .as_ref()
+                .map_or(latest.clone(), |info| info.version.clone()),
+        )
+        .and_then(|files| files.iter().map(|file| &file.upload_time_iso_8601).min())
+        .cloned();
+    let info = selected.unwrap_or(project.info);
+
+    let mut notes = Vec::new();
+    if let Some(released) = released {
+        notes.push(format!("Released {}", released));
+    }
+    if let Some(requires_python) = info.requires_python.filter(|r| !r.is_empty()) {
+        notes.push(format!("Requires Python {}", requires_python));
+    }
+    let deprecated = if info.yanked {
+        Some(format!(
+            "yanked{}",
+            info.yanked_reason
+                .map(|reason| format!(": {}", reason))
+                .unwrap_or_default()
+        ))
+    } else {
+        info.classifiers
+            .iter()
+            .any(|classifier| classifier == "Development Status :: 7 - Inactive")
+            .then(|| "the project is marked inactive".to_owned())
+    };
+    let project_urls = info.project_urls.unwrap_or_default();
+    let repository = project_urls
+        .iter()
+        .find(|(label, _)| {
+            let label = label.to_lowercase();
+            ["source", "repository", "code", "github"]
+                .iter()
+                .any(|word| label.contains(word))
+        })
+        .map(|(_, url)| url.clone());
+    let homepage = info
+        .home_page
+        .filter(|homepage| !homepage.is_empty())
+        .or_else(|| {
+            project_urls
+                .iter()
+                .find(|(label, _)| label.eq_ignore_ascii_case("homepage"))
+                .map(|(_, url)| url.clone())
+        });
+    // `license` is sometimes the whole license text
+    let license = info
+        .license_expression
+        .or(info.license)
+        .and_then(|license| license.lines().next().map(str::to_owned))
+        .filter(|license| !license.is_empty())
+        .map(|license| truncate(&license, 100));
+    Package {
+        registry: Registry::Pypi,
+        name: info.name,
+        version: info.version,
+        latest,
+        description: info.summary,
+        deprecated,
+        license,
+        repository,
+        homepage,
+        notes,
+        dependencies: info.requires_dist.unwrap_or_default(),
+        recent,
+        readme: info.description,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GoInfo {
+    version: String,
+    time: String,
+    origin: Option<GoOrigin>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GoOrigin {
+    #[serde(rename = "URL")]
+    url: Option<String>,
+}
+
+/// Looks up a module in the Go module proxy. pkg.go.dev links to packages, which may be
+/// in a subdirectory of the module, so without a version the path is shortened until a module
+/// is found. The proxy has neither licenses nor READMEs, so READMEs are read from GitHub for
+/// modules hosted there
+async fn fetch_go(
+    http: &HttpClient,
+    path: &str,
+    version: Option<&str>,
+) -> Result<Package, anyhow::Error> {
+    let mut module = path.trim_end_matches('/');
+    let info: GoInfo = loop {
+        let url = match version {
+            Some(version) => format!("{}/{}/@v/{}.info", GO_PROXY, escape_module(module), version),
+            None => format!("{}/{}/@latest", GO_PROXY, escape_module(module)),
+        };
+        match get_json(http, &url, || format!("Go module {}", path)).await {
+            Ok(info) => break info,
+            Err(err) => match module.rsplit_once('/') {
+                Some((parent, _)) if version.is_none() && parent.contains('/') => module = parent,
+                _ => return Err(err),
+            },
+        }
+    };
+    let escaped = escape_module(module);
+    let latest: GoInfo = match version {
+        Some(_) => {
+            get_json(http, &format!("{}/{}/@latest", GO_PROXY, escaped), || {
+                format!("Go module {}", module)
+            })
+            .await?
+        }
+        None => GoInfo {
+            version: info.version.clone(),
+            time: info.time.clone(),
+            origin: None,
+        },
+    };
+    let go_mod = get(
+        http,
+        &format!("{}/{}/@v/{}.mod", GO_PROXY, escaped, info.version),
+        || format!("go.mod of {}", module),
+    )
+    .await?
+    .text()
+    .await?;
+    let list = get(http, &format!("{}/{}/@v/list", GO_PROXY, escaped), || {
+        format!("Go module {}", module)
+    })
+    .await?
+    .text()
+    .await?;
+    let mut versions: Vec<&str> = list.lines().filter(|line| !line.is_empty()).collect();
+    versions.sort_by_key(|version| std::cmp::Reverse(version_key(version)));
+    versions.truncate(RECENT_VERSIONS);
+    // owned, as futures borrowing the closure's argument trip up the tools' Send bound
+    let requests: Vec<(String, String)> = versions
+        .iter()
+        .map(|version| {
+            let url = format!("{}/{}/@v/{}.info", GO_PROXY, escaped, version);
+            (version.to_string(), url)
+        })
+        .collect();
+    let recent: Vec<Release> =
+        futures::stream::iter(requests.into_iter().map(|(version, url)| async move {
+            Release {
+                version,
+                date: get_json::<GoInfo>(http, &url, String::new)
+                    .await
+                    .ok()
+                    .map(|info| info.time),
+                note: None,
+            }
+        }))
+        .buffered(GO_INFO_CONCURRENCY)
+        .collect()
+        .await;
+
+    let repository = info.origin.and_then(|origin| origin.url).or_else(|| {
+        github_repo(module).map(|(owner, repo, _)| format!("https://github.com/{}/{}", owner, repo))
+    });
+    let readme = match github_repo(module) {
+        Some((owner, repo, subdirectory)) => {
+            // modules in a subdirectory are tagged with its path, e.g. "tools/v1.2.0"
+            let (tag, readme) = match subdirectory {
+                Some(subdirectory) => (
+                    format!("{}/{}", subdirectory, info.version),
+                    format!("{}/README.md", subdirectory),
+                ),
+                None => (info.version.clone(), "README.md".to_owned()),
+            };
+            let url = format!(
+                "https://raw.githubusercontent.com/{}/{}/{}/{}",
+                owner, repo, tag, readme
+            );
+            match get(http, &url, String::new).await {
+                Ok(res) => res.text().await.ok(),
+                Err(_) => None,
+            }
+        }
+        None => None,
+    };
+    let (deprecated, dependencies) = parse_go_mod(&go_mod);
+    Ok(Package {
+        registry: Registry::Go,
+        name: module.to_owned(),
+        notes: vec![format!("Released {}", info.time)],
+        version: info.version,
+        latest: latest.version,
+        description: None,
+        deprecated,
+        license: None,
+        repository,
+        homepage: Some(format!("https://pkg.go.dev/{}", module)),
+        dependencies,
+        recent,
+        readme,
+    })
+}
+
+/// The module proxy escapes upper case letters as "!" and the lower case letter
+fn escape_module(module: &str) -> String {
+    let mut escaped = String::with_capacity(module.len());
+    for c in module.chars() {
+        if c.is_ascii_uppercase() {
+            escaped.push('!');
+            escaped.push(c.to_ascii_lowercase());
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+/// The owner, repo and subdirectory of a module hosted on GitHub. Major version suffixes
+/// (e.g. "/v2") aren't directories
+fn github_repo(module: &str) -> Option<(&str, &str, Option<String>)> {
+    let mut parts = module.strip_prefix("github.com/")?.split('/');
+    let owner = parts.next()?;
+    let repo = parts.next()?;
+    let rest: Vec<&str> = parts.collect();
+    let rest = match rest.split_last() {
+        Some((last, init))
+            if last
+                .strip_prefix('v')
+                .is_some_and(|n| n.parse::<u32>().is_ok()) =>
+        {
+            init
+        }
+        _ => &rest[..],
+    };
+    let subdirectory = (!rest.is_empty()).then(|| rest.join("/"));
+    Some((owner, repo, subdirectory))
+}
+
+/// Orders versions like semver: by their numbers, with releases after their pre-releases
+fn version_key(version: &str) -> (Vec<u64>, bool) {
+    let version = version.trim_start_matches('v');
+    let (release, pre) = match version.split_once('-') {
+        Some((release, _)) => (release, true),
+        None => (version, false),
+    };
+    let numbers = release
+        .split('.')
+        .map(|number| number.parse().unwrap_or(0))
+        .collect();
+    (numbers, !pre)
+}
+
+/// The deprecation notice and direct dependencies of a go.mod file
+fn parse_go_mod(go_mod: &str) -> (Option<String>, Vec<String>) {
+    let mut deprecated = None;
+    let mut comment = Vec::new();
+    let mut dependencies = Vec::new();
+    let mut in_require = false;
+    for line in go_mod.lines().map(str::trim) {
+        if let Some(text) = line.strip_prefix("//") {
+            comment.push(text.trim());
+            continue;
+        }
+        if line.starts_with("module ") {
+            // a "Deprecated:" paragraph in the comment on the module directive
+            deprecated = comment
+                .iter()
+                .position(|line| line.starts_with("Deprecated:"))
+                .map(|start| {
+                    comment[start..]
+                        .iter()
+                        .take_while(|line| !line.is_empty())
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                        .trim_start_matches("Deprecated:")
+                        .trim()
+                        .to_owned()
+                });
+        }
+        comment.clear();
+        let requirement = if in_require {
+            if line == ")" {
+                in_require = false;
+                continue;
+            }
+            line
+        } else if line == "require (" {
+            in_require = true;
+            continue;
+        } else if let Some(requirement) = line.strip_prefix("require ") {
+            requirement
+        } else {
+            continue;
+        };
+        if !requirement.is_empty() && !requirement.ends_with("// indirect") {
+            dependencies.push(requirement.to_owned());
+        }
+    }
+    (deprecated, dependencies)
+}
+
+#[derive(Deserialize)]
+struct SolrResponse {
+    response: SolrDocs,
+}
+
+#[derive(Deserialize)]
+struct SolrDocs {
+    docs: Vec<SolrDoc>,
+}
+
+#[derive(Deserialize)]
+struct SolrDoc {
+    v: String,
+    /// milliseconds since the Unix epoch
+    timestamp: i64,
+}
+
+/// Looks up an artifact on Maven Central. Versions come from its metadata, release dates from
+/// the search API, and everything else from the version's POM. Maven has no READMEs, and POMs
+/// inheriting their license from a parent POM show none
+async fn fetch_maven(
+    http: &HttpClient,
+    name: &str,
+    version: Option<&str>,
+) -> Result<Package, anyhow::Error> {
+    let (group, artifact) = name.split_once(':').ok_or_else(|| {
+        anyhow!("Maven artifacts are named group:artifact, e.g. com.google.guava:guava")
+    })?;
+    let base = format!("{}/{}/{}", MAVEN_REPO, group.replace('.', "/"), artifact);
+    let metadata = get(http, &format!("{}/maven-metadata.xml", base), || {
+        format!("Maven artifact {}", name)
+    })
+    .await?
+    .text()
+    .await?;
+    let versions = xml_blocks(&metadata, "version");
+    let latest = xml_text(&metadata, "release")
+        .or_else(|| versions.last().map(|version| version.to_string()))
+        .ok_or_else(|| anyhow!("{} has no versions", name))?;
+    let wanted = version.unwrap_or(&latest).to_owned();
+    let pom = get(
+        http,
+        &format!("{}/{}/{}-{}.pom", base, wanted, artifact, wanted),
+        || format!("version {} of {}", wanted, name),
+    )
+    .await?
+    .text()
+    .await?;
+    // dates are nice to have, so the search API being down isn't an error
+    let search = format!(
+        "https://search.maven.org/solrsearch/select?q=g:\"{}\" AND a:\"{}\"&core=gav&rows=50&wt=json",
+        group, artifact
+    );
+    let dates: HashMap<String, String> = get_json::<SolrResponse>(http, &search, String::new)
+        .await
+        .map(|solr| {
+            solr.response
+                .docs
+                .into_iter()
+                .map(|doc| (doc.v, date_from_millis(doc.timestamp)))
+                .collect()
+        })
+        .unwrap_or_default();
+    // the metadata lists versions in the order they were published
+    let recent = versions
+        .iter()
+        .rev()
+        .take(RECENT_VERSIONS)
+        .map(|version| Release {
+            version: version.to_string(),
+            date: dates.get(*version).cloned(),
+            note: None,
+        })
+        .collect();
+    let mut package = maven_package(name, &pom, wanted, latest);
+    package.recent = recent;
+    if let Some(released) = dates.get(&package.version) {
+        package.notes.insert(0, format!("Released {}", released));
+    }
+    Ok(package)
+}
+
+fn maven_package(name: &str, pom: &str, version: String, latest: String) -> Package {
+    // managed dependencies are constraints for other modules, not dependencies of this one
+    let pom = match (
+        pom.find("<dependencyManagement>"),
+        pom.find("</dependencyManagement>"),
+    ) {
+        (Some(start), Some(end)) if start < end => {
+            format!("{}{}", &pom[..start], &pom[end..])
+        }
+        _ => pom.to_owned(),
+    };
+    // the project's own elements come after its parent's
+    let own = pom
+        .find("</parent>")
+        .map_or(pom.as_str(), |end| &pom[end..]);
+    let licenses: Vec<String> = xml_blocks(&pom, "license")
+        .iter()
+        .filter_map(|license| xml_text(license, "name"))
+        .collect();
+    let dependencies = xml_blocks(&pom, "dependency")
+        .iter()
+        .map(|dependency| {
+            let field = |tag| xml_text(dependency, tag).unwrap_or_default();
+            let mut text = format!("{}:{}", field("groupId"), field("artifactId"));
+            if let Some(version) = xml_text(dependency, "version") {
+                let _ = write!(text, " {}", version);
+            }
+            let scope = field("scope");
+            if !scope.is_empty() && scope != "compile" {
+                let _ = write!(text, " ({})", scope);
+            }
+            if xml_text(dependency, "optional").as_deref() == Some("true") {
+                text.push_str(" (optional)");
+            }
+            text
+        })
+        .collect();
+    let deprecated = xml_blocks(&pom, "relocation").first().map(|relocation| {
+        let field = |tag| xml_text(relocation, tag).unwrap_or_else(|| "-".to_owned());
+        format!(
+            "relocated to {}:{}{}",
+            field("groupId"),
+            field("artifactId"),
+            xml_text(relocation, "message")
+                .map(|message| format!(", {}", message))
+                .unwrap_or_default()
+        )
+    });
+    Package {
+        registry: Registry::Maven,
+        name: name.to_owned(),
+        version,
+        latest,
+        description: xml_text(own, "description").or_else(|| xml_text(own, "name")),
+        deprecated,
+        license: (!licenses.is_empty()).then(|| licenses.join(", ")),
+        repository: xml_blocks(&pom, "scm")
+            .first()
+            .and_then(|scm| xml_text(scm, "url")),
+        homepage: xml_text(own, "url"),
+        notes: Vec::new(),
+        dependencies,
+        recent: Vec::new(),
+        readme: None,
+    }
+}
+
+/// The contents of each `<tag>` element in `xml`. Good enough for POMs and Maven metadata,
+/// which don't nest elements of the same name
+fn xml_blocks<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    let mut blocks = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let content = &rest[start + open.len()..];
+        let Some(end) = content.find(&close) else {
+            break;
+        };
+        blocks.push(&content[..end]);
+        rest = &content[end + close.len()..];
+    }
+    blocks
+}
+
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let text = strip_tags(xml_blocks(xml, tag).first()?);
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+fn date_from_millis(millis: i64) -> String {
+    let (year, month, day) = civil_from_days(millis.div_euclid(24 * 60 * 60 * 1000));
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn render(package: Package) -> String {
+    let mut text = format!(
+        "# {} {} ({})\n\n",
+        package.name,
+        package.version,
+        package.registry.name()
+    );
+    if let Some(description) = package.description.as_ref() {
+        let _ = writeln!(text, "{}\n", description.trim());
+    }
+    if let Some(deprecated) = package.deprecated.as_ref() {
+        let _ = writeln!(text, "**Deprecated**: {}\n", deprecated);
+    }
+    let _ = writeln!(text, "Latest version: {}", package.latest);
+    for note in package.notes.iter() {
+        let _ = writeln!(text, "{}", note);
+    }
+    let _ = writeln!(
+        text,
+        "License: {}",
+        package.license.as_deref().unwrap_or("unknown")
+    );
+    for (label, link) in [
+        ("Repository", &package.repository),
+        ("Homepage", &package.homepage),
+    ] {
+        if let Some(link) = link {
+            let _ = writeln!(text, "{}: {}", label, link);
+        }
+    }
+    if !package.dependencies.is_empty() {
+        let _ = writeln!(text, "\n## Dependencies\n");
+        for dependency in package.dependencies.iter() {
+            let _ = writeln!(text, "- {}", dependency);
+        }
+    }
+    if !package.recent.is_empty() {
+        let _ = writeln!(text, "\n## Recent versions\n");
+        for release in package.recent.iter() {
+            let _ = writeln!(
+                text,
+                "- {}{}{}",
+                release.version,
+                release
+                    .date
+                    .as_ref()
+                    .map(|date| format!(" ({})", date.get(..10).unwrap_or(date)))
+                    .unwrap_or_default(),
+                release
+                    .note
+                    .map(|note| format!(", {}", note))
+                    .unwrap_or_default()
+            );
+        }
+    }
+    if let Some(readme) = package.readme.filter(|readme| !readme.trim().is_empty()) {
+        let _ = write!(
+            text,
+            "\n## README\n\n{}",
+            truncate(readme.trim(), MAX_README_CHARS)
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_package_url() {
+        let parse = |url: &str| package_url(&Url::parse(url).unwrap());
+        assert_eq!(
+            parse("https://www.npmjs.com/package/@types/node/v/22.0.0"),
+            Some((Registry::Npm, "@types/node".into(), Some("22.0.0".into())))
+        );
+        assert_eq!(
+            parse("https://pypi.org/project/requests/"),
+            Some((Registry::Pypi, "requests".into(), None))
+        );
+        assert_eq!(
+            parse("https://pkg.go.dev/github.com/spf13/cobra@v1.9.1/doc"),
+            Some((
+                Registry::Go,
+                "github.com/spf13/cobra".into(),
+                Some("v1.9.1".into())
+            ))
+        );
+        assert_eq!(parse("https://pkg.go.dev/net/http"), None);
+        assert_eq!(
+            parse("https://central.sonatype.com/artifact/com.google.guava/guava/33.4.0-jre"),
+            Some((
+                Registry::Maven,
+                "com.google.guava:guava".into(),
+                Some("33.4.0-jre".into())
+            ))
+        );
+        assert_eq!(
+            escape_module("github.com/Azure/azure-sdk"),
+            "github.com/!azure/azure-sdk"
+        );
+        assert_eq!(
+            github_repo("github.com/o/r/tools/v2"),
+            Some(("o", "r", Some("tools".to_owned())))
+        );
+    }
+
+    #[test]
+    fn test_render_packages() {
+        let npm: NpmPackage =
+            serde_json::from_str(&fs::read_to_string("testdata/npm-package.json").unwrap())
+                .unwrap();
+        let text = render(npm_package(npm, Some("0.9.0")).unwrap());
+        assert!(text.starts_with("# left-pad-ng 0.9.0 (npm)"));
+        assert!(text.contains("**Deprecated**: use String.prototype.padStart"));
+        assert!(text.contains("Latest version: 1.1.0"));
+        assert!(text.contains("License: MIT"));
+        assert!(text.contains(
+            "- 1.1.0 (2024-05-02)\n- 1.0.0 (2024-01-10)\n- 0.9.0 (2023-06-01), deprecated"
+        ));
+
+        let pypi: PyPiResponse =
+            serde_json::from_str(&fs::read_to_string("testdata/pypi-project.json").unwrap())
+                .unwrap();
+        let text = render(pypi_package(pypi, None));
+        assert!(text.starts_with("# requests-lite 2.1.0 (PyPI)"));
+        assert!(text.contains("Requires Python >=3.9"));
+        assert!(text.contains("Repository: https://github.com/example/requests-lite"));
+        assert!(text.contains("- urllib3<3,>=1.21.1"));
+        assert!(text.contains("- 2.0.1 (2024-02-01), yanked"));
+        assert!(text.contains("## README\n\n# requests-lite"));
+
+        let (deprecated, dependencies) = parse_go_mod(
+            "// Deprecated: use example.com/new instead.\nmodule example.com/old\n\ngo 1.22\n\nrequire (\n\tgolang.org/x/sys v0.30.0\n\tgolang.org/x/text v0.22.0 // indirect\n)\n",
+        );
+        assert_eq!(deprecated.as_deref(), Some("use example.com/new instead."));
+        assert_eq!(dependencies, ["golang.org/x/sys v0.30.0"]);
+
+        let pom = "<project><parent><artifactId>parent</artifactId></parent><name>Guava</name>\
+            <licenses><license><name>Apache-2.0</name></license></licenses>\
+            <dependencyManagement><dependencies><dependency><groupId>x</groupId><artifactId>managed</artifactId></dependency></dependencies></dependencyManagement>\
+            <dependencies><dependency><groupId>com.google.code.findbugs</groupId><artifactId>jsr305</artifactId><version>3.0.2</version><scope>provided</scope></dependency></dependencies></project>";
+        let text = render(maven_package(
+            "com.google.guava:guava",
+            pom,
+            "33.4.0-jre".into(),
+            "33.4.0-jre".into(),
+        ));
+        assert!(text.contains("Guava\n"));
+        assert!(text.contains("License: Apache-2.0"));
+        assert!(text.contains("- com.google.code.findbugs:jsr305 3.0.2 (provided)"));
+        assert!(!text.contains("managed"));
+    }
+}
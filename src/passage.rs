@@ -0,0 +1,133 @@
+use std::{collections::HashSet, sync::LazyLock};
+
+use regex::Regex;
+
+static TAG_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^>]+>").unwrap());
+static BLOCK_TAG_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)</?(p|div|h[1-6]|li|ul|ol|pre|blockquote|tr|table|br)\b[^>]*>").unwrap()
+});
+static TERM_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[\p{L}\p{N}_]+").unwrap());
+
+/// Words too common to say anything about relevance
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "can", "do", "does", "for", "from", "how",
+    "i", "in", "is", "it", "of", "on", "or", "the", "to", "what", "when", "why", "with",
+];
+
+/// Removes HTML tags and decodes the entities search engines and Stack Exchange commonly use
+pub fn strip_tags(html: &str) -> String {
+    TAG_RE
+        .replace_all(html, "")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Converts HTML (e.g. a forum post) to Markdown-style plain text, keeping code, emphasis,
+/// lists and links
+pub fn html_to_markdown(html: &str) -> String {
+    html2text::config::plain()
+        .no_link_wrapping()
+        .no_table_borders()
+        .string_from_read(html.as_bytes(), 100)
+        .unwrap_or_else(|_| strip_tags(html))
+        .trim()
+        .to_owned()
+}
+
+/// Cuts `text` down to `max_chars`, saying how much was left out
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    let total = text.chars().count();
+    if total <= max_chars {
+        return text.to_owned();
+    }
+    let mut truncated: String = text.chars().take(max_chars).collect();
+    truncated.push_str(&format!(
+        "\n\n(truncated, {} more characters)",
+        total - max_chars
+    ));
+    truncated
+}
+
+/// Picks the run of consecutive paragraphs of `text` (HTML, Markdown or plain text) that is
+/// most relevant to `query` and fits in `max_chars`. Paragraphs are scored by how many of the
+/// query's distinct terms they contain, so a passage covering the whole question beats one
+/// repeating a single keyword. Falls back to the start of the text when nothing matches
+pub fn best_passage(text: &str, query: &str, max_chars: usize) -> String {
+    let terms = query_terms(query);
+    let text = strip_tags(&BLOCK_TAG_RE.replace_all(text, "\n\n"));
+    let paragraphs: Vec<String> = text
+        .split("\n\n")
+        .map(|paragraph| paragraph.trim())
+        .filter(|paragraph| !paragraph.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if paragraphs.is_empty() || max_chars == 0 {
+        return String::new();
+    }
+    let scores: Vec<usize> = paragraphs
+        .iter()
+        .map(|paragraph| {
+            let words = query_terms(paragraph);
+            terms.intersection(&words).count()
+        })
+        .collect();
+    let lengths: Vec<usize> = paragraphs.iter().map(|p| p.chars().count()).collect();
+
+    // the best window of paragraphs, as (score, start, end)
+    let mut best = (0, 0, 1);
+    for start in 0..paragraphs.len() {
+        let mut score = 0;
+        let mut length = 0;
+        for end in start..paragraphs.len() {
+            // paragraphs are joined by a blank line
+            length += lengths[end] + if end > start { 2 } else { 0 };
+            if length > max_chars && end > start {
+                break;
+            }
+            score += scores[end];
+            if score > best.0 {
+                best = (score, start, end + 1);
+            }
+        }
+    }
+    let passage = paragraphs[best.1..best.2].join("\n\n");
+    if passage.chars().count() <= max_chars {
+        return passage;
+    }
+    let mut truncated: String = passage.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+fn query_terms(text: &str) -> HashSet<String> {
+    TERM_RE
+        .find_iter(text)
+        .map(|term| term.as_str().to_lowercase())
+        .filter(|term| !STOP_WORDS.contains(&term.as_str()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_best_passage() {
+        let page = "<h1>Intro</h1><p>Tokio is an asynchronous runtime.</p>\
+            <p>Unrelated text about installation and licensing.</p>\
+            <p>To cancel a task in a <code>select!</code> loop, keep the future pinned.</p>\
+            <p>The select macro also works with channels.</p>";
+        let passage = best_passage(page, "tokio select loop cancel", 120);
+        assert!(passage.starts_with("To cancel a task"));
+        assert!(!passage.contains("<code>"));
+        assert!(!passage.contains("licensing"));
+        assert!(passage.chars().count() <= 120);
+
+        // no matches at all keeps the start of the page
+        assert_eq!(best_passage(page, "webassembly", 10), "Intro");
+        assert_eq!(best_passage(&"x".repeat(50), "x", 10).chars().count(), 10);
+    }
+}
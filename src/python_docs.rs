@@ -0,0 +1,395 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{Context, anyhow};
+use regex::Regex;
+use url::Url;
+
+use crate::passage::truncate;
+
+/// A documented object's signature line, e.g. `awaitable asyncio.gather(*aws)` or `os.sep`
+static ENTRY_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(?:(class|exception|coroutine|awaitable|async|abstractmethod|classmethod|staticmethod|property|type|data)\s+)*([A-Za-z_][\w.]*)(\(.*|\[.*|: .*| = .*)?$",
+    )
+    .unwrap()
+});
+/// The title of a module's page, e.g. `"asyncio" — Asynchronous I/O`
+static MODULE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^"?([A-Za-z_][\w.]*)"? — "#).unwrap());
+
+/// Long pages (e.g. the built-in functions) are narrowed with a fragment, or cut off
+const MAX_PAGE_CHARS: usize = 40_000;
+
+/// How far the Sphinx text builder indents an object's description
+const INDENT: usize = 3;
+
+/// A module, class, function or attribute documented in the docs tree
+#[derive(Debug, PartialEq, Eq)]
+struct Entry {
+    name: String,
+    kind: &'static str,
+    /// the page's path in the tree, without the extension, e.g. "library/asyncio-task"
+    page: String,
+}
+
+/// Python documentation built locally with the Sphinx text builder (`make text`), or downloaded
+/// as the plain text archive from docs.python.org. The path is either one build, used for every
+/// version, or a directory of builds named by version, e.g. `3.12` and `3.13`
+pub struct PythonDocs {
+    root: PathBuf,
+    /// the objects of each build, indexed the first time it's searched
+    indexes: tokio::sync::Mutex<HashMap<PathBuf, Arc<Vec<Entry>>>>,
+}
+
+impl PythonDocs {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            indexes: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The build for `version` and the version it's published under, where "3" (or no version)
+    /// is the newest build
+    async fn build(&self, version: Option<&str>) -> Result<(PathBuf, String), anyhow::Error> {
+        let root = self.root.clone();
+        let version = version.map(str::to_owned);
+        tokio::task::spawn_blocking(move || find_build(&root, version.as_deref())).await?
+    }
+
+    /// Reads the page of a docs.python.org URL. A fragment naming an object (e.g.
+    /// `#asyncio.gather`) or a section narrows the page to its description
+    pub async fn page(&self, url: &Url) -> Result<String, anyhow::Error> {
+        let (version, path) = page_path(url).ok_or_else(|| {
+            anyhow!("invalid docs.python.org URL: must be a page of the English docs")
+        })?;
+        let (build, _) = self.build(version.as_deref()).await?;
+        let text = tokio::fs::read_to_string(build.join(&path))
+            .await
+            .with_context(|| format!("unable to read Python docs page {}", path))?;
+        let narrowed = url.fragment().and_then(|fragment| {
+            object_section(&text, fragment).or_else(|| heading_section(&text, fragment))
+        });
+        Ok(truncate(narrowed.unwrap_or(&text).trim(), MAX_PAGE_CHARS))
+    }
+
+    /// Finds modules, classes, functions and attributes by name, e.g. "gather", "asyncio.gather"
+    /// or "pathlib". Exact matches come first, then names ending in the query, then names
+    /// containing it
+    pub async fn search(
+        &self,
+        query: &str,
+        version: Option<&str>,
+        num: usize,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let (build, version) = self.build(version).await?;
+        let index = self.index(build).await?;
+        let query = query.trim().to_lowercase();
+        let suffix = format!(".{}", query);
+        let mut matches: Vec<(u8, &Entry)> = index
+            .iter()
+            .filter_map(|entry| {
+                let name = entry.name.to_lowercase();
+                let rank = if name == query {
+                    0
+                } else if name.ends_with(&suffix) {
+                    1
+                } else if name.contains(&query) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, entry))
+            })
+            .collect();
+        matches.sort_by_key(|(rank, entry)| (*rank, entry.name.len(), entry.name.as_str()));
+        Ok(matches
+            .into_iter()
+            .take(num)
+            .map(|(_, entry)| {
+                // modules are anchored as "module-asyncio", everything else by its name
+                let anchor = match entry.kind {
+                    "module" => format!("module-{}", entry.name),
+                    _ => entry.name.clone(),
+                };
+                format!(
+                    "{} ({}) https://docs.python.org/{}/{}.html#{}",
+                    entry.name, entry.kind, version, entry.page, anchor
+                )
+            })
+            .collect())
+    }
+
+    async fn index(&self, build: PathBuf) -> Result<Arc<Vec<Entry>>, anyhow::Error> {
+        let mut indexes = self.indexes.lock().await;
+        if let Some(index) = indexes.get(&build) {
+            return Ok(index.clone());
+        }
+        let root = build.clone();
+        let index = Arc::new(tokio::task::spawn_blocking(move || index_tree(&root)).await??);
+        indexes.insert(build, index.clone());
+        Ok(index)
+    }
+}
+
+/// `PythonDocs::build`, which lists the builds and so runs on a blocking thread
+fn find_build(root: &Path, version: Option<&str>) -> Result<(PathBuf, String), anyhow::Error> {
+    if root.join("library").is_dir() {
+        return Ok((root.to_owned(), version.unwrap_or("3").to_owned()));
+    }
+    let mut versions: Vec<String> = fs::read_dir(root)
+        .context("unable to read the Python docs directory")?
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| version_key(name).is_some())
+        .collect();
+    versions.sort_by_key(|name| version_key(name));
+    let version = match version {
+        None | Some("3") => versions.last(),
+        Some(version) => versions.iter().find(|name| *name == version),
+    }
+    .ok_or_else(|| {
+        anyhow!(
+            "no local Python docs for version {}, available: {}",
+            version.unwrap_or("3"),
+            versions.join(", ")
+        )
+    })?;
+    Ok((root.join(version), version.clone()))
+}
+
+/// Orders version directories, e.g. "3.9" before "3.12"
+fn version_key(name: &str) -> Option<(u32, u32)> {
+    let (major, minor) = name.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// The version (None for "/3/") and text file of a docs.python.org URL, e.g.
+/// `/3.12/library/asyncio-task.html` is version 3.12's `library/asyncio-task.txt`
+fn page_path(url: &Url) -> Option<(Option<String>, String)> {
+    let mut segments: Vec<&str> = url.path_segments()?.collect();
+    let version = match segments.first() {
+        Some(&"3") => {
+            segments.remove(0);
+            None
+        }
+        Some(version) if version_key(version).is_some() => Some(segments.remove(0).to_owned()),
+        // translations start with a language code, e.g. "fr" or "pt-br", and aren't supported
+        Some(first) if first.len() == 2 || (first.len() == 5 && first.as_bytes()[2] == b'-') => {
+            return None;
+        }
+        _ => None,
+    };
+    let mut path = segments.join("/");
+    if path.is_empty() || path.ends_with('/') {
+        path.push_str("index.html");
+    }
+    let page = path.strip_suffix(".html")?;
+    // the path comes from a URL, so make sure it stays in the tree
+    if page
+        .split('/')
+        .any(|segment| segment == ".." || segment.is_empty())
+    {
+        return None;
+    }
+    Some((version, format!("{}.txt", page)))
+}
+
+fn index_tree(build: &Path) -> Result<Vec<Entry>, anyhow::Error> {
+    let mut entries = Vec::new();
+    let mut directories = vec![build.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        for file in fs::read_dir(&directory)? {
+            let path = file?.path();
+            if path.is_dir() {
+                directories.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "txt") {
+                let Ok(text) = fs::read_to_string(&path) else {
+                    continue;
+                };
+                let page = path
+                    .strip_prefix(build)?
+                    .with_extension("")
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                entries.extend(
+                    page_entries(&text)
+                        .into_iter()
+                        .map(|(name, kind, _)| Entry {
+                            name,
+                            kind,
+                            page: page.clone(),
+                        }),
+                );
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// The objects described on a page, with the line each starts on. The text builder writes a
+/// signature followed by its description indented one level deeper. Methods and attributes
+/// are nested in their class, and named after it
+fn page_entries(text: &str) -> Vec<(String, &'static str, usize)> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut entries = Vec::new();
+    if let Some(module) = lines.first().and_then(|title| MODULE_RE.captures(title)) {
+        entries.push((module[1].to_owned(), "module", 0));
+    }
+    // the enclosing classes, as (indent, name)
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    for (number, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let indent = indentation(line);
+        let Some(next) = lines[number + 1..]
+            .iter()
+            .find(|line| !line.trim().is_empty())
+        else {
+            break;
+        };
+        if indentation(next) != indent + INDENT {
+            continue;
+        }
+        let Some(captures) = ENTRY_RE.captures(line.trim()) else {
+            continue;
+        };
+        parents.retain(|(parent_indent, _)| *parent_indent < indent);
+        let mut name = captures[2].to_owned();
+        if let Some((_, parent)) = parents.last()
+            && !name.contains('.')
+        {
+            name = format!("{}.{}", parent, name);
+        }
+        let nested = !parents.is_empty();
+        let kind = match captures.get(1).map(|keyword| keyword.as_str()) {
+            Some("class") => "class",
+            Some("exception") => "exception",
+            _ if captures
+                .get(3)
+                .is_some_and(|rest| rest.as_str().starts_with('(')) =>
+            {
+                if nested {
+                    "method"
+                } else {
+                    "function"
+                }
+            }
+            _ if nested => "attribute",
+            _ => "data",
+        };
+        if matches!(kind, "class" | "exception") {
+            parents.push((indent, name.clone()));
+        }
+        entries.push((name, kind, number));
+    }
+    entries
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The description of the object named `fragment`, e.g. "asyncio.gather"
+fn object_section<'a>(text: &'a str, fragment: &str) -> Option<&'a str> {
+    let (_, _, start) = page_entries(text)
+        .into_iter()
+        .find(|(name, kind, _)| name == fragment && *kind != "module")?;
+    let lines: Vec<&str> = text.lines().collect();
+    let indent = indentation(lines[start]);
+    let end = lines[start + 1..]
+        .iter()
+        .position(|line| !line.trim().is_empty() && indentation(line) <= indent)
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    Some(line_span(text, &lines, start, end))
+}
+
+/// The section whose heading becomes `fragment` as an anchor, e.g. "running-an-asyncio-program",
+/// up to the next heading of the same level
+fn heading_section<'a>(text: &'a str, fragment: &str) -> Option<&'a str> {
+    let lines: Vec<&str> = text.lines().collect();
+    let underline = |line: &str| {
+        let mut chars = line.chars();
+        let first = chars.next().filter(|c| "*=-~^\"".contains(*c))?;
+        (line.len() >= 3 && chars.all(|c| c == first)).then_some(first)
+    };
+    let start = (0..lines.len().saturating_sub(1)).find(|&number| {
+        underline(lines[number + 1]).is_some() && slug(lines[number]) == fragment
+    })?;
+    let level = underline(lines[start + 1]);
+    let end = (start + 2..lines.len().saturating_sub(1))
+        .find(|&number| underline(lines[number + 1]) == level && !lines[number].trim().is_empty())
+        .unwrap_or(lines.len());
+    Some(line_span(text, &lines, start, end))
+}
+
+/// The anchor Sphinx gives a heading
+fn slug(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// The text of lines `start..end`, as a slice of `text`
+fn line_span<'a>(text: &'a str, lines: &[&'a str], start: usize, end: usize) -> &'a str {
+    let offset = |line: &str| line.as_ptr() as usize - text.as_ptr() as usize;
+    let from = offset(lines[start]);
+    let to = lines.get(end).map_or(text.len(), |line| offset(line));
+    &text[from..to]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_python_docs() {
+        let docs = PythonDocs::new(PathBuf::from("testdata/python-docs"));
+        let page = |url: &str| {
+            let url = Url::parse(url).unwrap();
+            let docs = &docs;
+            async move { docs.page(&url).await.unwrap() }
+        };
+        // "/3/" is the newest version
+        let text = page("https://docs.python.org/3/library/asyncio-task.html").await;
+        assert!(text.contains("eager_start"));
+        let text =
+            page("https://docs.python.org/3.12/library/asyncio-task.html#asyncio.gather").await;
+        assert!(text.starts_with("awaitable asyncio.gather(*aws, return_exceptions=False)"));
+        assert!(!text.contains("eager_start") && !text.contains("asyncio.sleep"));
+        let text =
+            page("https://docs.python.org/3.12/library/asyncio-task.html#asyncio.Task.cancel")
+                .await;
+        assert!(text.trim_start().starts_with("cancel(msg=None)"));
+        let text = page(
+            "https://docs.python.org/3.12/library/asyncio-task.html#running-tasks-concurrently",
+        )
+        .await;
+        assert!(text.starts_with("Running Tasks Concurrently\n"));
+        assert!(!text.contains("Sleeping"));
+        assert!(
+            docs.page(&Url::parse("https://docs.python.org/3.11/library/os.html").unwrap())
+                .await
+                .is_err()
+        );
+
+        let results = docs.search("cancel", Some("3.12"), 5).await.unwrap();
+        assert_eq!(
+            results[0],
+            "asyncio.Task.cancel (method) https://docs.python.org/3.12/library/asyncio-task.html#asyncio.Task.cancel"
+        );
+        let results = docs.search("asyncio", None, 1).await.unwrap();
+        assert_eq!(
+            results,
+            ["asyncio (module) https://docs.python.org/3.13/library/asyncio.html#module-asyncio"]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Google resets API quotas at midnight Pacific time. DST is ignored, so the count may roll
+/// over an hour off for part of the year
+const QUOTA_DAY_OFFSET_SECS: u64 = 8 * 60 * 60;
+
+/// The share of the quota at which we start warning
+const WARN_AT: f64 = 0.8;
+
+/// Counts requests against a daily quota, so we can warn before the upstream starts refusing
+pub struct DailyQuota {
+    limit: u32,
+    /// (day number, requests made that day)
+    state: Mutex<(u64, u32)>,
+}
+
+/// How the quota looks after recording a request
+#[derive(Debug, PartialEq, Eq)]
+pub enum QuotaStatus {
+    Ok,
+    /// at or past the warning threshold, with this many requests left
+    Low(u32),
+    /// at or over the limit. Requests may still succeed if the configured limit is lower than
+    /// the real one
+    Exhausted,
+}
+
+impl DailyQuota {
+    /// A `limit` of 0 disables the counter
+    pub fn new(limit: u32) -> Self {
+        Self {
+            limit,
+            state: Mutex::new((current_day(), 0)),
+        }
+    }
+
+    pub fn record(&self) -> QuotaStatus {
+        self.record_on(current_day())
+    }
+
+    /// A warning for the logs and the model once the quota runs low, without recording a
+    /// request
+    pub fn warning(&self) -> Option<String> {
+        self.warning_on(current_day())
+    }
+
+    fn record_on(&self, day: u64) -> QuotaStatus {
+        if self.limit == 0 {
+            return QuotaStatus::Ok;
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.0 != day {
+            *state = (day, 0);
+        }
+        state.1 += 1;
+        self.status(state.1)
+    }
+
+    fn warning_on(&self, day: u64) -> Option<String> {
+        let used = match *self.state.lock().unwrap() {
+            (state_day, used) if state_day == day => used,
+            _ => 0,
+        };
+        match self.status(used) {
+            QuotaStatus::Ok => None,
+            QuotaStatus::Low(left) => {
+                Some(format!("{} Google searches left in today's quota", left))
+            }
+            QuotaStatus::Exhausted => Some(format!(
+                "the daily Google search quota of {} has been used up",
+                self.limit
+            )),
+        }
+    }
+
+    fn status(&self, used: u32) -> QuotaStatus {
+        if self.limit == 0 {
+            QuotaStatus::Ok
+        } else if used >= self.limit {
+            QuotaStatus::Exhausted
+        } else if used as f64 >= self.limit as f64 * WARN_AT {
+            QuotaStatus::Low(self.limit - used)
+        } else {
+            QuotaStatus::Ok
+        }
+    }
+}
+
+fn current_day() -> u64 {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    secs.saturating_sub(QUOTA_DAY_OFFSET_SECS) / (24 * 60 * 60)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_daily_quota() {
+        let quota = DailyQuota::new(10);
+        for _ in 0..7 {
+            assert_eq!(quota.record_on(1), QuotaStatus::Ok);
+        }
+        assert_eq!(quota.warning_on(1), None);
+        assert_eq!(quota.record_on(1), QuotaStatus::Low(2));
+        assert_eq!(
+            quota.warning_on(1).unwrap(),
+            "2 Google searches left in today's quota"
+        );
+        assert_eq!(quota.record_on(1), QuotaStatus::Low(1));
+        assert_eq!(quota.record_on(1), QuotaStatus::Exhausted);
+        // a new day resets the count
+        assert_eq!(quota.warning_on(2), None);
+        assert_eq!(quota.record_on(2), QuotaStatus::Ok);
+
+        let disabled = DailyQuota::new(0);
+        assert_eq!(disabled.record_on(1), QuotaStatus::Ok);
+    }
+}
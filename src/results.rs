@@ -0,0 +1,438 @@
+use std::{collections::HashMap, fmt::Write, sync::LazyLock};
+
+use regex::Regex;
+use rmcp::schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{FetchHandler, GoogleSearchResult, GoogleSearchResults, PageMap, cache::canonical_url};
+
+/// The k of reciprocal rank fusion. It damps the weight of the top ranks, so a page found by
+/// several queries beats one that only a single query ranked first
+const RRF_K: f64 = 60.0;
+
+/// Google prefixes many snippets with the page's date, e.g. "Aug 28, 2024 ... "
+static SNIPPET_DATE_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?P<date>[A-Z][a-z]{2} \d{1,2}, \d{4}) \.\.\. ").unwrap());
+
+/// How search results are rendered for the model
+#[derive(Deserialize, JsonSchema, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    /// a JSON object with a list of results
+    #[default]
+    Json,
+    /// a compact plain text list, one short block per result
+    Text,
+}
+
+/// The kind of site a result comes from, which determines how it can be fetched and what the
+/// extra fields mean
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceKind {
+    #[serde(rename = "so")]
+    StackOverflow,
+    Mdn,
+    Reddit,
+    Forum,
+    Other,
+}
+
+impl SourceKind {
+    fn label(&self) -> &'static str {
+        match self {
+            SourceKind::StackOverflow => "so",
+            SourceKind::Mdn => "mdn",
+            SourceKind::Reddit => "reddit",
+            SourceKind::Forum => "forum",
+            SourceKind::Other => "other",
+        }
+    }
+}
+
+/// A single search result, reduced to what's useful for deciding whether to read the page
+#[derive(Serialize, Deserialize)]
+pub struct SearchResult {
+    pub rank: usize,
+    pub title: String,
+    pub url: String,
+    pub source: SourceKind,
+    pub snippet: String,
+    /// whether `fetch_web_page` can retrieve this page. Not cached, as it depends on the
+    /// server's configuration
+    #[serde(default)]
+    pub fetchable: bool,
+    /// how `fetch_web_page` would retrieve this page
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub handler: Option<FetchHandler>,
+    /// the date the search engine reported for the page, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// votes on the question (SO)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub votes: Option<i64>,
+    /// number of answers to the question (SO)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub answers: Option<usize>,
+    /// votes on the best answer (SO)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_answer_votes: Option<i64>,
+    /// whether the question has an accepted answer, when Google reports it (SO)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accepted: Option<bool>,
+    /// when the first post of the thread was published (forums)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub posted: Option<String>,
+    /// number of replies included in the page (forums)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replies: Option<usize>,
+}
+
+/// A normalized page of search results
+#[derive(Serialize, Deserialize)]
+pub struct SearchResults {
+    pub results: Vec<SearchResult>,
+    /// the `start` to pass to get the next page of results, if there is one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_start: Option<usize>,
+}
+
+impl SearchResults {
+    /// `offset` is the index of the first result, i.e. the `start` parameter of the search
+    pub fn from_google(results: GoogleSearchResults, offset: usize) -> Self {
+        let next_start = results
+            .queries
+            .next_page
+            .as_ref()
+            .and_then(|pages| pages.first())
+            .map(|page| page.start_index);
+        Self {
+            results: results
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+                .map(|(index, item)| SearchResult::from_google(item, offset + index))
+                .collect(),
+            next_start,
+        }
+    }
+
+    /// Merges several result lists into the best `limit` results by reciprocal rank fusion.
+    /// Results are deduplicated by canonical URL, keeping the copy from the list that ranked
+    /// the page highest
+    pub fn fuse(lists: Vec<SearchResults>, limit: usize) -> Self {
+        let mut fused: Vec<(f64, usize, SearchResult)> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for list in lists {
+            for (position, result) in list.results.into_iter().enumerate() {
+                let score = 1.0 / (RRF_K + position as f64 + 1.0);
+                let key = Url::parse(&result.url)
+                    .map(|url| canonical_url(&url))
+                    .unwrap_or_else(|_| result.url.clone());
+                match seen.get(&key) {
+                    Some(&index) => {
+                        let entry = &mut fused[index];
+                        entry.0 += score;
+                        if position < entry.1 {
+                            entry.1 = position;
+                            entry.2 = result;
+                        }
+                    }
+                    None => {
+                        seen.insert(key, fused.len());
+                        fused.push((score, position, result));
+                    }
+                }
+            }
+        }
+        // a stable sort, so ties keep the order the pages were first seen in
+        fused.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Self {
+            results: fused
+                .into_iter()
+                .take(limit)
+                .enumerate()
+                .map(|(index, (_, _, mut result))| {
+                    result.rank = index + 1;
+                    result
+                })
+                .collect(),
+            next_start: None,
+        }
+    }
+
+    /// Marks each result with the handler that would fetch it, dropping those that can't be
+    /// fetched if `only_fetchable` is set
+    pub fn mark_fetchable(
+        &mut self,
+        handler: impl Fn(&Url) -> Option<FetchHandler>,
+        only_fetchable: bool,
+    ) {
+        for result in self.results.iter_mut() {
+            result.handler = Url::parse(&result.url).ok().and_then(|url| handler(&url));
+            result.fetchable = result.handler.is_some();
+        }
+        if only_fetchable {
+            // ranks continue from the page's first, which is past 1 on later pages
+            let first = self.results.first().map_or(1, |result| result.rank);
+            self.results.retain(|result| result.fetchable);
+            for (index, result) in self.results.iter_mut().enumerate() {
+                result.rank = first + index;
+            }
+        }
+    }
+
+    pub fn render(&self, format: ResultFormat) -> String {
+        match format {
+            ResultFormat::Json => serde_json::to_string(self).unwrap(),
+            ResultFormat::Text => self.render_text(),
+        }
+    }
+
+    fn render_text(&self) -> String {
+        if self.results.is_empty() {
+            return "No results".to_owned();
+        }
+        let mut text = String::new();
+        for result in self.results.iter() {
+            let _ = writeln!(
+                text,
+                "{}. [{}] {}\n   {}{}",
+                result.rank,
+                result.source.label(),
+                result.title,
+                result.url,
+                if result.fetchable {
+                    ""
+                } else {
+                    " (can't be fetched)"
+                }
+            );
+            let mut extras = Vec::new();
+            if let Some(date) = result.date.as_ref().or(result.posted.as_ref()) {
+                extras.push(date.clone());
+            }
+            if let Some(votes) = result.votes {
+                extras.push(format!("{} votes", votes));
+            }
+            if let Some(answers) = result.answers {
+                extras.push(format!("{} answers", answers));
+            }
+            if let Some(votes) = result.top_answer_votes {
+                extras.push(format!("top answer {} votes", votes));
+            }
+            if let Some(true) = result.accepted {
+                extras.push("accepted answer".to_owned());
+            }
+            if let Some(replies) = result.replies {
+                extras.push(format!("{} replies", replies));
+            }
+            if !extras.is_empty() {
+                let _ = writeln!(text, "   {}", extras.join(", "));
+            }
+            let _ = writeln!(text, "   {}", result.snippet);
+        }
+        if let Some(next_start) = self.next_start {
+            let _ = writeln!(text, "More results: start={}", next_start);
+        }
+        text
+    }
+}
+
+impl SearchResult {
+    /// A result with only what every search engine reports. The source kind is guessed from
+    /// the host
+    pub fn new(
+        rank: usize,
+        title: String,
+        url: String,
+        snippet: &str,
+        date: Option<String>,
+    ) -> Self {
+        let (snippet_date, snippet) = clean_snippet(snippet);
+        SearchResult {
+            rank,
+            source: source_kind(&url, None),
+            title,
+            url,
+            snippet,
+            fetchable: false,
+            handler: None,
+            date: date.or(snippet_date),
+            votes: None,
+            answers: None,
+            top_answer_votes: None,
+            accepted: None,
+            posted: None,
+            replies: None,
+        }
+    }
+
+    fn from_google(item: GoogleSearchResult, index: usize) -> Self {
+        let mut result = SearchResult::new(index + 1, item.title, item.link, &item.snippet, None);
+        result.source = source_kind(&result.url, Some(&item.pagemap));
+        match item.pagemap {
+            PageMap::StackOverflow(so) => {
+                let question = so.question.first();
+                result.votes = question.and_then(|q| q.upvote_count.parse().ok());
+                result.answers = question
+                    .and_then(|q| q.answer_count.as_ref())
+                    .and_then(|count| count.parse().ok());
+                result.top_answer_votes = so
+                    .accepted_answer
+                    .iter()
+                    .flatten()
+                    .chain(so.answer.iter())
+                    .filter_map(|answer| answer.upvote_count.parse().ok())
+                    .max();
+                result.accepted = so
+                    .accepted_answer
+                    .as_ref()
+                    .map(|accepted| !accepted.is_empty());
+            }
+            PageMap::ForumPost(forum) => {
+                result.posted = forum
+                    .forum_post
+                    .first()
+                    .map(|post| post.date_published.clone());
+                result.replies = forum.comment.as_ref().map(Vec::len);
+            }
+            _ => {}
+        }
+        result
+    }
+}
+
+fn source_kind(link: &str, pagemap: Option<&PageMap>) -> SourceKind {
+    let host = Url::parse(link)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned));
+    match host.as_deref() {
+        Some("stackoverflow.com") => SourceKind::StackOverflow,
+        Some("developer.mozilla.org") => SourceKind::Mdn,
+        Some("www.reddit.com") | Some("old.reddit.com") | Some("reddit.com") => SourceKind::Reddit,
+        _ if matches!(pagemap, Some(PageMap::ForumPost(_))) => SourceKind::Forum,
+        _ => SourceKind::Other,
+    }
+}
+
+/// Splits off the leading date Google adds to snippets, and normalizes whitespace (including
+/// the non-breaking spaces Google puts before ellipses)
+fn clean_snippet(snippet: &str) -> (Option<String>, String) {
+    let snippet = snippet
+        .replace('\u{a0}', " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    match SNIPPET_DATE_RE.captures(&snippet) {
+        Some(captures) => {
+            let date = captures["date"].to_owned();
+            let rest = snippet[captures.get(0).unwrap().end()..].to_owned();
+            (Some(date), rest)
+        }
+        None => (None, snippet),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+
+    fn load(path: &str) -> SearchResults {
+        let data = fs::read_to_string(path).unwrap();
+        SearchResults::from_google(serde_json::from_str(&data).unwrap(), 0)
+    }
+
+    #[test]
+    fn test_from_google() {
+        let results = load("testdata/sample.json");
+        assert_eq!(results.results.len(), 10);
+        assert_eq!(results.next_start, Some(11));
+        let first = &results.results[0];
+        assert_eq!(first.rank, 1);
+        assert_eq!(first.source, SourceKind::StackOverflow);
+        assert_eq!(first.votes, Some(23));
+        assert_eq!(first.answers, Some(2));
+        assert_eq!(first.top_answer_votes, Some(27));
+
+        let results = load("testdata/sample3.json");
+        assert_eq!(results.results[0].source, SourceKind::Reddit);
+
+        let results = load("testdata/sample4.json");
+        let forum = &results.results[0];
+        assert_eq!(forum.source, SourceKind::Forum);
+        assert_eq!(forum.posted.as_deref(), Some("2024-08-28T20:55:57Z"));
+        assert_eq!(forum.date.as_deref(), Some("Aug 28, 2024"));
+        assert!(forum.snippet.starts_with("Have it in a proper typed form"));
+        assert!(!forum.snippet.contains('\u{a0}'));
+        assert_eq!(results.results[2].source, SourceKind::Other);
+
+        let text = results.render(ResultFormat::Text);
+        assert!(text.starts_with("1. [forum] Efficiently embedding large static JSON data"));
+    }
+
+    #[test]
+    fn test_fuse() {
+        let result = |url: &str| SearchResult::new(1, url.to_owned(), url.to_owned(), "", None);
+        let list = |urls: &[&str]| SearchResults {
+            results: urls.iter().map(|url| result(url)).collect(),
+            next_start: None,
+        };
+        let fused = SearchResults::fuse(
+            vec![
+                list(&[
+                    "https://a.com/",
+                    "https://b.com/x?utm_source=g",
+                    "https://c.com/",
+                ]),
+                list(&["https://b.com/x", "https://d.com/"]),
+                list(&["https://e.com/", "https://b.com/x#top", "https://a.com"]),
+            ],
+            3,
+        );
+        let urls: Vec<_> = fused.results.iter().map(|r| r.url.as_str()).collect();
+        // b is found by every query, a by two, and e beats d on its rank alone
+        assert_eq!(
+            urls,
+            ["https://b.com/x", "https://a.com/", "https://e.com/"]
+        );
+        assert_eq!(fused.results[2].rank, 3);
+    }
+
+    #[test]
+    fn test_mark_fetchable() {
+        let only_so = |url: &Url| {
+            (url.host_str() == Some("stackoverflow.com")).then_some(FetchHandler::StackOverflow)
+        };
+        let mut results = load("testdata/sample4.json");
+        results.mark_fetchable(only_so, false);
+        assert_eq!(results.results.len(), 10);
+        assert!(!results.results[0].fetchable);
+        assert!(
+            results
+                .render(ResultFormat::Text)
+                .contains("116722 (can't be fetched)")
+        );
+
+        results.mark_fetchable(only_so, true);
+        assert_eq!(results.results.len(), 1);
+        assert_eq!(
+            results.results[0].handler,
+            Some(FetchHandler::StackOverflow)
+        );
+        // the remaining results are ranked without gaps
+        assert_eq!(results.results[0].rank, 1);
+
+        // on the second page, from its start
+        let data = fs::read_to_string("testdata/sample4.json").unwrap();
+        let mut results = SearchResults::from_google(serde_json::from_str(&data).unwrap(), 10);
+        assert_eq!(results.results[0].rank, 11);
+        results.mark_fetchable(only_so, true);
+        assert_eq!(results.results.len(), 1);
+        assert_eq!(results.results[0].rank, 11);
+    }
+}
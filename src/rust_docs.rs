@@ -0,0 +1,963 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, anyhow};
+use serde::Deserialize;
+use serde_json::Value;
+use url::Url;
+
+use crate::{
+    crates::{docs_content, find_item},
+    passage::{html_to_markdown, truncate},
+};
+
+/// The crates the rust-docs component documents
+const CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+/// Docs of large items (e.g. `Vec`) run long, mostly trait impls
+const MAX_DOCS_CHARS: usize = 40_000;
+
+/// The most trait implementations listed for a type
+const MAX_TRAIT_IMPLS: usize = 60;
+
+#[derive(Deserialize)]
+struct Crate {
+    crate_version: Option<String>,
+    index: HashMap<String, Item>,
+    paths: HashMap<String, Summary>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    name: Option<String>,
+    docs: Option<String>,
+    /// strings in older formats, objects (with the unparsed ones under "other") in newer ones
+    #[serde(default)]
+    attrs: Vec<Value>,
+    deprecation: Option<Deprecation>,
+    /// the kind of item and its details, e.g. `{"function": {...}}`
+    inner: Value,
+}
+
+#[derive(Deserialize)]
+struct Deprecation {
+    since: Option<String>,
+    note: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Summary {
+    crate_id: u32,
+    path: Vec<String>,
+}
+
+/// The docs of a Rust toolchain, from the `share/doc/rust` directory of a rustup toolchain.
+/// The rust-docs component provides the HTML docs and books under `html`, and the
+/// rust-docs-json component (nightly only) rustdoc JSON under `json`, which is preferred for
+/// API items as it renders more compactly
+pub struct RustDocs {
+    root: PathBuf,
+    /// the rustdoc JSON of each crate, loaded when first used, or None if it isn't installed
+    crates: HashMap<&'static str, tokio::sync::OnceCell<Option<Arc<Crate>>>>,
+}
+
+/// An item of the standard library, e.g. `std::sync::Mutex::lock`, as a path to an item and
+/// optionally a member of it (a method, field or variant), which has no page of its own
+#[derive(Debug, PartialEq, Eq)]
+struct ItemPath {
+    krate: String,
+    path: Vec<String>,
+    member: Option<String>,
+}
+
+/// What a doc.rust-lang.org URL refers to
+#[derive(Debug, PartialEq, Eq)]
+enum DocPage {
+    /// a page of the API docs, with the item it documents
+    Api { file: String, item: ItemPath },
+    /// a page of one of the books, e.g. `book/ch04-01-what-is-ownership.html`
+    Book { file: String },
+}
+
+impl RustDocs {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            crates: CRATES
+                .iter()
+                .map(|name| (*name, tokio::sync::OnceCell::new()))
+                .collect(),
+        }
+    }
+
+    /// Reads the page of a doc.rust-lang.org URL from the local docs. Channel and version
+    /// prefixes (e.g. `/stable/` or `/1.80.0/`) are ignored, as the local toolchain is the one
+    /// that matters
+    pub async fn page(&self, url: &Url) -> Result<Vec<String>, anyhow::Error> {
+        let page = doc_page(url).ok_or_else(|| {
+            anyhow!("invalid doc.rust-lang.org URL: must be an API docs or book page")
+        })?;
+        match page {
+            DocPage::Api { file, item } => {
+                if let Some(krate) = self.krate(&item.krate).await?
+                    && let Some(parts) = render_path(&krate, &item)
+                {
+                    return Ok(parts);
+                }
+                let html = self.read_html(&file).await?;
+                Ok(vec![html_item(&html, item.member.as_deref())?])
+            }
+            DocPage::Book { file } => {
+                let html = self.read_html(&file).await?;
+                let start = html.find("<main").unwrap_or(0);
+                let end = html[start..]
+                    .find("</main>")
+                    .map_or(html.len(), |end| start + end);
+                Ok(vec![truncate(
+                    &html_to_markdown(&html[start..end]),
+                    MAX_DOCS_CHARS,
+                )])
+            }
+        }
+    }
+
+    /// Looks up an item by path, e.g. `std::sync::Mutex` or `std::sync::Mutex::lock`
+    pub async fn lookup(&self, path: &str) -> Result<Vec<String>, anyhow::Error> {
+        let segments: Vec<String> = path
+            .trim()
+            .split("::")
+            .map(|segment| segment.trim().to_owned())
+            .collect();
+        // segments become file paths below, so anything but identifiers (e.g. "..") is refused
+        let Some((krate, rest)) = segments
+            .split_first()
+            .filter(|(krate, _)| CRATES.contains(&krate.as_str()))
+            .filter(|_| segments.iter().all(|segment| is_identifier(segment)))
+        else {
+            return Err(anyhow!(
+                "paths must be identifiers separated by :: starting with one of {}, e.g. \
+                 std::sync::Mutex::lock",
+                CRATES.join(", ")
+            ));
+        };
+        if let Some(json) = self.krate(krate).await? {
+            let item = ItemPath {
+                krate: krate.clone(),
+                path: rest.to_vec(),
+                member: None,
+            };
+            if let Some(parts) = render_path(&json, &item) {
+                return Ok(parts);
+            }
+            if let Some((member, parent)) = rest.split_last() {
+                let item = ItemPath {
+                    krate: krate.clone(),
+                    path: parent.to_vec(),
+                    member: Some(member.clone()),
+                };
+                if let Some(parts) = render_path(&json, &item) {
+                    return Ok(parts);
+                }
+            }
+        }
+        // without JSON, find the item's page in the list of all items
+        let all = self.read_html(&format!("{}/all.html", krate)).await?;
+        if rest.is_empty() {
+            let html = self.read_html(&format!("{}/index.html", krate)).await?;
+            return Ok(vec![html_item(&html, None)?]);
+        }
+        for members in 0..=1.min(rest.len() - 1) {
+            let (item, member) = rest.split_at(rest.len() - members);
+            let (name, modules) = item.split_last().unwrap();
+            if let Some(page) = find_item(&all, modules, name) {
+                let html = self.read_html(&format!("{}/{}", krate, page)).await?;
+                return Ok(vec![html_item(&html, member.first().map(String::as_str))?]);
+            }
+        }
+        let module = format!("{}/{}/index.html", krate, rest.join("/"));
+        match self.read_html(&module).await {
+            Ok(html) => Ok(vec![html_item(&html, None)?]),
+            Err(_) => Err(anyhow!("no item {} in the local Rust docs", path)),
+        }
+    }
+
+    async fn read_html(&self, file: &str) -> Result<String, anyhow::Error> {
+        tokio::fs::read_to_string(self.root.join("html").join(file))
+            .await
+            .with_context(|| format!("unable to read local Rust docs page {}", file))
+    }
+
+    async fn krate(&self, name: &str) -> Result<Option<Arc<Crate>>, anyhow::Error> {
+        let Some(cell) = self.crates.get(name) else {
+            return Ok(None);
+        };
+        let krate = cell
+            .get_or_try_init(|| async {
+                let path = self.root.join("json").join(format!("{}.json", name));
+                tokio::task::spawn_blocking(move || {
+                    if !path.is_file() {
+                        return Ok(None);
+                    }
+                    Ok::<_, anyhow::Error>(Some(Arc::new(load_crate(&path)?)))
+                })
+                .await?
+            })
+            .await?;
+        Ok(krate.clone())
+    }
+}
+
+/// Whether a path segment is a plain Rust identifier, e.g. `Mutex` or `lock`
+fn is_identifier(segment: &str) -> bool {
+    let mut chars = segment.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn load_crate(path: &Path) -> Result<Crate, anyhow::Error> {
+    let file = fs::File::open(path)?;
+    serde_json::from_reader(BufReader::new(file))
+        .with_context(|| format!("unable to parse rustdoc JSON {}", path.display()))
+}
+
+/// Maps a doc.rust-lang.org URL to a file in the local docs
+fn doc_page(url: &Url) -> Option<DocPage> {
+    let mut segments: Vec<&str> = url.path_segments()?.filter(|s| !s.is_empty()).collect();
+    if segments.contains(&"..") {
+        return None;
+    }
+    // channels and versions, e.g. "stable", "nightly" or "1.80.0"
+    if segments.first().is_some_and(|first| {
+        matches!(*first, "stable" | "beta" | "nightly")
+            || first.split('.').all(|part| part.parse::<u32>().is_ok())
+    }) {
+        segments.remove(0);
+    }
+    let first = *segments.first()?;
+    let mut file = segments.join("/");
+    if url.path().ends_with('/') || segments.len() == 1 && !first.ends_with(".html") {
+        file.push_str("/index.html");
+    }
+    if !CRATES.contains(&first) {
+        return Some(DocPage::Book { file });
+    }
+    let mut path: Vec<String> = segments[1..].iter().map(|s| s.to_string()).collect();
+    match path.pop() {
+        // "struct.Mutex.html" is the item Mutex of the module path before it
+        Some(page) if page != "index.html" => {
+            let (_, name) = page.strip_suffix(".html")?.split_once('.')?;
+            path.push(name.to_owned());
+        }
+        _ => {}
+    }
+    let member = url.fragment().and_then(|fragment| {
+        let (kind, name) = fragment.split_once('.')?;
+        matches!(
+            kind,
+            "method"
+                | "tymethod"
+                | "structfield"
+                | "variant"
+                | "associatedtype"
+                | "associatedconstant"
+        )
+        .then(|| name.to_owned())
+    });
+    Some(DocPage::Api {
+        file,
+        item: ItemPath {
+            krate: first.to_owned(),
+            path,
+            member,
+        },
+    })
+}
+
+/// Reduces an HTML API page to its content, or to the section of one member of the item
+fn html_item(html: &str, member: Option<&str>) -> Result<String, anyhow::Error> {
+    let Some(member) = member else {
+        return docs_content(html);
+    };
+    let start = ["method", "tymethod", "structfield", "variant"]
+        .iter()
+        .find_map(|kind| html.find(&format!("id=\"{}.{}\"", kind, member)))
+        .ok_or_else(|| anyhow!("the page has no member {}", member))?;
+    // members are a <details> with the signature as its summary, and the docs after it
+    let start = html[..start]
+        .rfind("<details")
+        .filter(|details| start - details < 300)
+        .or_else(|| html[..start].rfind('<'))
+        .unwrap_or(start);
+    let end = html[start..]
+        .find("</details>")
+        .map_or(html.len(), |end| start + end);
+    Ok(truncate(
+        &html_to_markdown(&html[start..end]),
+        MAX_DOCS_CHARS,
+    ))
+}
+
+/// Renders the item at `item`'s path from rustdoc JSON, or None if the crate has no such item
+fn render_path(krate: &Crate, item: &ItemPath) -> Option<Vec<String>> {
+    let mut full: Vec<String> = vec![item.krate.clone()];
+    full.extend(item.path.iter().cloned());
+    let (id, _) = krate
+        .paths
+        .iter()
+        .find(|(_, summary)| summary.crate_id == 0 && summary.path == full)?;
+    let found = krate.index.get(id)?;
+    let mut parts = Vec::new();
+    match item.member.as_ref() {
+        Some(member) => {
+            let member_item = members(krate, found)
+                .into_iter()
+                .find(|candidate| candidate.name.as_deref() == Some(member))?;
+            parts.push(render_item(
+                krate,
+                &format!("{}::{}", full.join("::"), member),
+                member_item,
+            ));
+        }
+        None => {
+            parts.push(render_item(krate, &full.join("::"), found));
+            if let Some(details) = render_members(krate, found) {
+                parts.push(details);
+            }
+        }
+    }
+    Some(parts)
+}
+
+/// The items inside an item: the methods and associated items of a type's inherent impls, a
+/// trait's items, a module's items or an enum's variants
+fn members<'a>(krate: &'a Crate, item: &'a Item) -> Vec<&'a Item> {
+    let (kind, inner) = kind(item);
+    let ids = |field: &str| -> Vec<&'a Item> {
+        inner[field]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|id| krate.index.get(&id_key(id)))
+            .collect()
+    };
+    match kind {
+        "struct" | "enum" | "union" | "primitive" => {
+            let mut members = Vec::new();
+            if kind == "enum" {
+                members.extend(ids("variants"));
+            }
+            for implementation in ids("impls") {
+                let (_, details) = self::kind(implementation);
+                if !details["trait"].is_null() {
+                    continue;
+                }
+                members.extend(
+                    details["items"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|id| krate.index.get(&id_key(id))),
+                );
+            }
+            members
+        }
+        "trait" | "module" => ids("items"),
+        _ => Vec::new(),
+    }
+}
+
+fn render_item(krate: &Crate, path: &str, item: &Item) -> String {
+    let (kind, _) = kind(item);
+    let mut text = format!("# {} {}\n\n", kind, path);
+    if let Some(version) = krate.crate_version.as_ref() {
+        let _ = writeln!(text, "Local docs for Rust {}", version);
+    }
+    for attr in stability(item) {
+        let _ = writeln!(text, "{}", attr);
+    }
+    if let Some(deprecation) = item.deprecation.as_ref() {
+        let _ = writeln!(
+            text,
+            "Deprecated{}{}",
+            deprecation
+                .since
+                .as_ref()
+                .map(|since| format!(" since {}", since))
+                .unwrap_or_default(),
+            deprecation
+                .note
+                .as_ref()
+                .map(|note| format!(": {}", note))
+                .unwrap_or_default()
+        );
+    }
+    if let Some(signature) = signature(item) {
+        let _ = writeln!(text, "\n```rust\n{}\n```", signature);
+    }
+    if let Some(docs) = item.docs.as_ref() {
+        let _ = write!(text, "\n{}", truncate(docs.trim(), MAX_DOCS_CHARS));
+    }
+    text
+}
+
+/// Lists an item's members by their signatures, and for types, the traits they implement
+fn render_members(krate: &Crate, item: &Item) -> Option<String> {
+    let (kind, inner) = kind(item);
+    let mut text = String::new();
+    let members = members(krate, item);
+    if !members.is_empty() {
+        let _ = writeln!(
+            text,
+            "## {}\n",
+            match kind {
+                "module" => "Items",
+                "enum" => "Variants and methods",
+                _ => "Methods and associated items",
+            }
+        );
+        for member in members {
+            let line = match kind {
+                "module" => format!(
+                    "{} {}",
+                    self::kind(member).0,
+                    member.name.as_deref().unwrap_or("_")
+                ),
+                _ => signature(member).unwrap_or_else(|| member.name.clone().unwrap_or_default()),
+            };
+            // the first line of the docs says what it's for
+            let summary = member
+                .docs
+                .as_deref()
+                .and_then(|docs| docs.lines().next())
+                .filter(|line| !line.is_empty())
+                .map(|line| format!(": {}", line))
+                .unwrap_or_default();
+            let _ = writeln!(text, "- `{}`{}", line.replace('\n', " "), summary);
+        }
+    }
+    let traits: Vec<String> = inner["impls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|id| krate.index.get(&id_key(id)))
+        .filter(|implementation| !self::kind(implementation).1["trait"].is_null())
+        .filter_map(signature)
+        .collect();
+    if !traits.is_empty() {
+        let _ = writeln!(text, "\n## Trait implementations\n");
+        for implementation in traits.iter().take(MAX_TRAIT_IMPLS) {
+            let _ = writeln!(text, "- `{}`", implementation);
+        }
+        if traits.len() > MAX_TRAIT_IMPLS {
+            let _ = writeln!(text, "- ({} more)", traits.len() - MAX_TRAIT_IMPLS);
+        }
+    }
+    (!text.is_empty()).then_some(text)
+}
+
+/// The stability and deprecation attributes of an item, which rustdoc keeps as written
+fn stability(item: &Item) -> Vec<String> {
+    item.attrs
+        .iter()
+        .filter_map(|attr| match attr {
+            Value::String(attr) => Some(attr.as_str()),
+            attr => attr["other"].as_str(),
+        })
+        .filter(|attr| {
+            [
+                "stable(",
+                "unstable(",
+                "rustc_const_stable(",
+                "rustc_const_unstable(",
+            ]
+            .iter()
+            .any(|name| attr.contains(name))
+        })
+        .map(str::to_owned)
+        .collect()
+}
+
+/// The kind of an item, e.g. "struct", and its details
+fn kind(item: &Item) -> (&str, &Value) {
+    match &item.inner {
+        Value::Object(inner) => inner
+            .iter()
+            .next()
+            .map_or(("item", &Value::Null), |(kind, details)| {
+                (kind.as_str(), details)
+            }),
+        Value::String(kind) => (kind.as_str(), &Value::Null),
+        _ => ("item", &Value::Null),
+    }
+}
+
+/// Ids are numbers in newer formats and strings in older ones
+fn id_key(id: &Value) -> String {
+    match id {
+        Value::String(id) => id.clone(),
+        id => id.to_string(),
+    }
+}
+
+/// The first of `names` an object has, as formats have renamed fields over time
+fn field<'a>(value: &'a Value, names: &[&str]) -> &'a Value {
+    names
+        .iter()
+        .map(|name| &value[*name])
+        .find(|field| !field.is_null())
+        .unwrap_or(&Value::Null)
+}
+
+fn flag(value: &Value, names: &[&str]) -> bool {
+    field(value, names).as_bool().unwrap_or(false)
+}
+
+/// The declaration of an item, as it would be written in code
+fn signature(item: &Item) -> Option<String> {
+    let name = item.name.as_deref().unwrap_or("_");
+    let (kind, inner) = kind(item);
+    let signature = match kind {
+        "function" => function(name, inner),
+        "struct" | "enum" | "union" => {
+            let (params, wheres) = generics(&inner["generics"]);
+            format!("pub {} {}{}{}", kind, name, params, wheres)
+        }
+        "trait" => {
+            let (params, wheres) = generics(&inner["generics"]);
+            let bounds = bounds(&inner["bounds"]);
+            format!(
+                "pub {}{}trait {}{}{}{}",
+                if flag(inner, &["is_unsafe"]) {
+                    "unsafe "
+                } else {
+                    ""
+                },
+                if flag(inner, &["is_auto"]) {
+                    "auto "
+                } else {
+                    ""
+                },
+                name,
+                params,
+                if bounds.is_empty() {
+                    String::new()
+                } else {
+                    format!(": {}", bounds)
+                },
+                wheres
+            )
+        }
+        "impl" => {
+            let (params, wheres) = generics(&inner["generics"]);
+            let mut text = format!("impl{} ", params);
+            if flag(inner, &["is_negative", "negative"]) {
+                text.push('!');
+            }
+            if !inner["trait"].is_null() {
+                let _ = write!(text, "{} for ", path(&inner["trait"]));
+            }
+            let _ = write!(text, "{}{}", ty(&inner["for"]), wheres);
+            text
+        }
+        "struct_field" => format!("pub {}: {}", name, ty(inner)),
+        "variant" => {
+            let fields = match &inner["kind"] {
+                Value::Object(kind) if kind.contains_key("tuple") => "(..)",
+                Value::Object(kind) if kind.contains_key("struct") => " { .. }",
+                _ => "",
+            };
+            format!("{}{}", name, fields)
+        }
+        "type_alias" | "typedef" | "assoc_type" => {
+            let (params, _) = generics(&inner["generics"]);
+            let target = field(inner, &["type", "type_", "default"]);
+            let bounds = bounds(&inner["bounds"]);
+            let mut text = format!("type {}{}", name, params);
+            if !bounds.is_empty() {
+                let _ = write!(text, ": {}", bounds);
+            }
+            if !target.is_null() {
+                let _ = write!(text, " = {}", ty(target));
+            }
+            text
+        }
+        "constant" | "assoc_const" => {
+            let constant_type = field(inner, &["type", "type_"]);
+            format!("const {}: {}", name, ty(constant_type))
+        }
+        "static" => format!(
+            "static {}{}: {}",
+            if flag(inner, &["is_mutable", "mutable"]) {
+                "mut "
+            } else {
+                ""
+            },
+            name,
+            ty(field(inner, &["type", "type_"]))
+        ),
+        "macro" => inner
+            .as_str()
+            .map_or(format!("macro_rules! {}", name), str::to_owned),
+        "module" => format!("mod {}", name),
+        _ => return None,
+    };
+    Some(signature)
+}
+
+fn function(name: &str, inner: &Value) -> String {
+    let header = &inner["header"];
+    let sig = field(inner, &["sig", "decl"]);
+    let (params, wheres) = generics(&inner["generics"]);
+    let inputs: Vec<String> = sig["inputs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|input| {
+            let name = input[0].as_str().unwrap_or("_");
+            let input_type = &input[1];
+            if name == "self" {
+                match (ty(input_type).as_str(), input_type.get("borrowed_ref")) {
+                    ("Self", _) => return "self".to_owned(),
+                    (_, Some(reference)) if ty(field(reference, &["type", "type_"])) == "Self" => {
+                        return format!(
+                            "&{}{}self",
+                            lifetime(&reference["lifetime"]),
+                            if flag(reference, &["is_mutable", "mutable"]) {
+                                "mut "
+                            } else {
+                                ""
+                            }
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            format!("{}: {}", name, ty(input_type))
+        })
+        .collect();
+    let mut text = String::from("pub ");
+    for (names, keyword) in [
+        (&["is_const", "const_"][..], "const "),
+        (&["is_async", "async_"][..], "async "),
+        (&["is_unsafe", "unsafe_"][..], "unsafe "),
+    ] {
+        if flag(header, names) {
+            text.push_str(keyword);
+        }
+    }
+    let _ = write!(text, "fn {}{}({})", name, params, inputs.join(", "));
+    if !sig["output"].is_null() {
+        let _ = write!(text, " -> {}", ty(&sig["output"]));
+    }
+    text.push_str(&wheres);
+    text
+}
+
+fn lifetime(lifetime: &Value) -> String {
+    lifetime
+        .as_str()
+        .map(|lifetime| format!("{} ", lifetime))
+        .unwrap_or_default()
+}
+
+/// Renders a type from its JSON representation
+fn ty(value: &Value) -> String {
+    let Some((kind, inner)) = value.as_object().and_then(|object| object.iter().next()) else {
+        return value.as_str().unwrap_or("_").to_owned();
+    };
+    match kind.as_str() {
+        "resolved_path" => path(inner),
+        "generic" | "primitive" => inner.as_str().unwrap_or("_").to_owned(),
+        "borrowed_ref" => format!(
+            "&{}{}{}",
+            lifetime(&inner["lifetime"]),
+            if flag(inner, &["is_mutable", "mutable"]) {
+                "mut "
+            } else {
+                ""
+            },
+            ty(field(inner, &["type", "type_"]))
+        ),
+        "raw_pointer" => format!(
+            "*{} {}",
+            if flag(inner, &["is_mutable", "mutable"]) {
+                "mut"
+            } else {
+                "const"
+            },
+            ty(field(inner, &["type", "type_"]))
+        ),
+        "slice" => format!("[{}]", ty(inner)),
+        "array" => format!(
+            "[{}; {}]",
+            ty(field(inner, &["type", "type_"])),
+            inner["len"].as_str().unwrap_or("_")
+        ),
+        "tuple" => {
+            let types: Vec<String> = inner.as_array().into_iter().flatten().map(ty).collect();
+            if types.len() == 1 {
+                format!("({},)", types[0])
+            } else {
+                format!("({})", types.join(", "))
+            }
+        }
+        "impl_trait" => format!("impl {}", bounds(inner)),
+        "dyn_trait" => {
+            let mut traits: Vec<String> = inner["traits"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|poly| path(&poly["trait"]))
+                .collect();
+            if let Some(lifetime) = inner["lifetime"].as_str() {
+                traits.push(lifetime.to_owned());
+            }
+            format!("dyn {}", traits.join(" + "))
+        }
+        "qualified_path" => {
+            let self_type = ty(&inner["self_type"]);
+            let name = inner["name"].as_str().unwrap_or("_");
+            if inner["trait"].is_null() {
+                format!("{}::{}", self_type, name)
+            } else {
+                format!("<{} as {}>::{}", self_type, path(&inner["trait"]), name)
+            }
+        }
+        "function_pointer" => {
+            let sig = field(inner, &["sig", "decl"]);
+            let inputs: Vec<String> = sig["inputs"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|input| ty(&input[1]))
+                .collect();
+            let mut text = format!("fn({})", inputs.join(", "));
+            if !sig["output"].is_null() {
+                let _ = write!(text, " -> {}", ty(&sig["output"]));
+            }
+            text
+        }
+        "pat" => ty(field(inner, &["type", "type_"])),
+        "infer" => "_".to_owned(),
+        _ => "_".to_owned(),
+    }
+}
+
+/// A path to a type or trait with its generic arguments, e.g. `Option<T>` or `Fn(u8) -> u8`
+fn path(value: &Value) -> String {
+    let name = field(value, &["path", "name"])
+        .as_str()
+        .unwrap_or("_")
+        .to_owned();
+    let args = &value["args"];
+    if let Some(angle) = args.get("angle_bracketed") {
+        let mut items: Vec<String> = angle["args"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|arg| match arg {
+                Value::Object(arg) => match arg.iter().next() {
+                    Some((kind, value)) if kind == "type" => ty(value),
+                    Some((kind, value)) if kind == "lifetime" => {
+                        value.as_str().unwrap_or("'_").to_owned()
+                    }
+                    Some((_, value)) => value["expr"].as_str().unwrap_or("_").to_owned(),
+                    None => "_".to_owned(),
+                },
+                _ => "_".to_owned(),
+            })
+            .collect();
+        for constraint in field(angle, &["constraints", "bindings"])
+            .as_array()
+            .into_iter()
+            .flatten()
+        {
+            let name = constraint["name"].as_str().unwrap_or("_");
+            let binding = &constraint["binding"];
+            if let Some(equality) = binding.get("equality") {
+                let value = equality.get("type").map_or_else(|| ty(equality), ty);
+                items.push(format!("{} = {}", name, value));
+            } else if let Some(constraint) = binding.get("constraint") {
+                items.push(format!("{}: {}", name, bounds(constraint)));
+            }
+        }
+        if items.is_empty() {
+            name
+        } else {
+            format!("{}<{}>", name, items.join(", "))
+        }
+    } else if let Some(parenthesized) = args.get("parenthesized") {
+        let inputs: Vec<String> = parenthesized["inputs"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(ty)
+            .collect();
+        let mut text = format!("{}({})", name, inputs.join(", "));
+        if !parenthesized["output"].is_null() {
+            let _ = write!(text, " -> {}", ty(&parenthesized["output"]));
+        }
+        text
+    } else {
+        name
+    }
+}
+
+fn bounds(value: &Value) -> String {
+    let bounds: Vec<String> = value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|bound| {
+            if let Some(trait_bound) = bound.get("trait_bound") {
+                let maybe = trait_bound["modifier"].as_str() == Some("maybe");
+                Some(format!(
+                    "{}{}",
+                    if maybe { "?" } else { "" },
+                    path(&trait_bound["trait"])
+                ))
+            } else {
+                bound["outlives"].as_str().map(str::to_owned)
+            }
+        })
+        .collect();
+    bounds.join(" + ")
+}
+
+/// The generic parameters and where clause of an item, leaving out the parameters the
+/// compiler adds for `impl Trait` arguments
+fn generics(generics: &Value) -> (String, String) {
+    let params: Vec<String> = generics["params"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|param| {
+            let name = param["name"].as_str().unwrap_or("_");
+            let kind = &param["kind"];
+            if let Some(lifetime) = kind.get("lifetime") {
+                let outlives: Vec<&str> = lifetime["outlives"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(Value::as_str)
+                    .collect();
+                return Some(if outlives.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}: {}", name, outlives.join(" + "))
+                });
+            }
+            if let Some(type_param) = kind.get("type") {
+                if flag(type_param, &["is_synthetic", "synthetic"]) {
+                    return None;
+                }
+                let bounds = bounds(&type_param["bounds"]);
+                return Some(if bounds.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}: {}", name, bounds)
+                });
+            }
+            let const_type = field(&kind["const"], &["type", "type_"]);
+            Some(format!("const {}: {}", name, ty(const_type)))
+        })
+        .collect();
+    let predicates: Vec<String> = generics["where_predicates"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|predicate| {
+            let bound = predicate.get("bound_predicate")?;
+            Some(format!(
+                "{}: {}",
+                ty(field(bound, &["type", "type_"])),
+                bounds(&bound["bounds"])
+            ))
+        })
+        .collect();
+    (
+        if params.is_empty() {
+            String::new()
+        } else {
+            format!("<{}>", params.join(", "))
+        },
+        if predicates.is_empty() {
+            String::new()
+        } else {
+            format!("\nwhere\n    {}", predicates.join(",\n    "))
+        },
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_doc_page() {
+        let page = |url: &str| doc_page(&Url::parse(url).unwrap()).unwrap();
+        assert_eq!(
+            page("https://doc.rust-lang.org/stable/std/sync/struct.Mutex.html#method.lock"),
+            DocPage::Api {
+                file: "std/sync/struct.Mutex.html".into(),
+                item: ItemPath {
+                    krate: "std".into(),
+                    path: vec!["sync".into(), "Mutex".into()],
+                    member: Some("lock".into()),
+                },
+            }
+        );
+        assert_eq!(
+            page("https://doc.rust-lang.org/1.80.0/core/mem/index.html"),
+            DocPage::Api {
+                file: "core/mem/index.html".into(),
+                item: ItemPath {
+                    krate: "core".into(),
+                    path: vec!["mem".into()],
+                    member: None,
+                },
+            }
+        );
+        assert_eq!(
+            page("https://doc.rust-lang.org/book/"),
+            DocPage::Book {
+                file: "book/index.html".into()
+            }
+        );
+        assert_eq!(
+            page("https://doc.rust-lang.org/nomicon/races.html"),
+            DocPage::Book {
+                file: "nomicon/races.html".into()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_render_json() {
+        let docs = RustDocs::new(PathBuf::from("testdata/rust-docs"));
+        let parts = docs.lookup("std::sync::Mutex").await.unwrap();
+        assert!(parts[0].starts_with("# struct std::sync::Mutex\n\nLocal docs for Rust 1.89.0"));
+        assert!(parts[0].contains("#[stable(feature = \"rust1\", since = \"1.0.0\")]"));
+        assert!(parts[0].contains("pub struct Mutex<T: ?Sized>"));
+        assert!(parts[1].contains(
+            "- `pub fn lock(&self) -> LockResult<MutexGuard<'_, T>>`: Acquires a mutex, blocking the current thread until it is able to do so."
+        ));
+        assert!(parts[1].contains("- `impl<T: ?Sized + Send> Send for Mutex<T>`"));
+
+        let parts = docs.lookup("std::sync::Mutex::lock").await.unwrap();
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].starts_with("# function std::sync::Mutex::lock"));
+        assert!(parts[0].contains("This function will block the local thread"));
+        assert!(docs.lookup("serde::Deserialize").await.is_err());
+        assert!(docs.lookup("std::..::..::etc").await.is_err());
+        assert!(docs.lookup("").await.is_err());
+    }
+}
@@ -0,0 +1,720 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use reqwest::StatusCode;
+use rmcp::{ErrorData, schemars::JsonSchema};
+use serde::Deserialize;
+
+use crate::{
+    GoogleSearchResults, SearchParams, SiteSearchFilter, google_error,
+    http::HttpClient,
+    passage::strip_tags,
+    quota::DailyQuota,
+    results::{SearchResult, SearchResults},
+};
+
+/// The search engines that can back `query_google_search`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BackendKind {
+    /// the Google Custom Search JSON API
+    Google,
+    /// a SearXNG instance, with the JSON format enabled
+    Searxng,
+    /// the Brave Search API
+    Brave,
+    /// the Bing Web Search API
+    Bing,
+}
+
+/// A configured search engine
+pub enum SearchBackend {
+    Google {
+        engine_id: String,
+        api_key: String,
+        quota: DailyQuota,
+    },
+    Searxng {
+        /// the instance's base URL, e.g. http://localhost:8888
+        url: String,
+    },
+    Brave {
+        api_key: String,
+    },
+    Bing {
+        api_key: String,
+        endpoint: String,
+    },
+}
+
+/// Search backends in order of preference. Each is only tried if all the ones before it failed
+pub struct SearchChain {
+    backends: Vec<SearchBackend>,
+}
+
+impl SearchChain {
+    pub fn new(backends: Vec<SearchBackend>) -> Self {
+        Self { backends }
+    }
+
+    pub fn has(&self, kind: BackendKind) -> bool {
+        self.backends.iter().any(|backend| backend.kind() == kind)
+    }
+
+    /// The backends used, either the whole chain or `only` the one of that kind
+    fn backends(&self, only: Option<BackendKind>) -> impl Iterator<Item = &SearchBackend> {
+        self.backends
+            .iter()
+            .filter(move |backend| only.is_none_or(|kind| backend.kind() == kind))
+    }
+
+    /// A warning about the Google quota running low, if Google is among the backends used, so
+    /// tools can pass it on to the model spending it
+    pub fn quota_warning(&self, only: Option<BackendKind>) -> Option<String> {
+        self.backends(only).find_map(|backend| match backend {
+            SearchBackend::Google { quota, .. } => quota.warning(),
+            _ => None,
+        })
+    }
+
+    /// The backend names joined with "+", used to keep results of different chains apart in the
+    /// cache
+    pub fn name(&self, only: Option<BackendKind>) -> String {
+        self.backends(only)
+            .map(SearchBackend::name)
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+
+    /// Searches with each backend in turn until one succeeds, or with `only` the backend of that
+    /// kind. If they all fail, the error of each is returned, so the model knows whether
+    /// searching again is worth it
+    pub async fn search(
+        &self,
+        http: &HttpClient,
+        params: &SearchParams,
+        only: Option<BackendKind>,
+    ) -> Result<SearchResults, ErrorData> {
+        let mut errors = Vec::new();
+        for backend in self.backends(only) {
+            match backend.search(http, params).await {
+                Ok(results) => return Ok(results),
+                Err(err) => {
+                    eprintln!("{} search failed: {}", backend.name(), err.message);
+                    errors.push((backend.name(), err));
+                }
+            }
+        }
+        match errors.len() {
+            0 => Err(ErrorData::internal_error(
+                "no search backends are configured",
+                None,
+            )),
+            1 => Err(errors.pop().unwrap().1),
+            _ => Err(ErrorData::internal_error(
+                errors
+                    .into_iter()
+                    .map(|(name, err)| format!("{}: {}", name, err.message))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+                None,
+            )),
+        }
+    }
+}
+
+impl SearchBackend {
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            SearchBackend::Google { .. } => BackendKind::Google,
+            SearchBackend::Searxng { .. } => BackendKind::Searxng,
+            SearchBackend::Brave { .. } => BackendKind::Brave,
+            SearchBackend::Bing { .. } => BackendKind::Bing,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind() {
+            BackendKind::Google => "google",
+            BackendKind::Searxng => "searxng",
+            BackendKind::Brave => "brave",
+            BackendKind::Bing => "bing",
+        }
+    }
+
+    pub async fn search(
+        &self,
+        http: &HttpClient,
+        params: &SearchParams,
+    ) -> Result<SearchResults, ErrorData> {
+        match self {
+            SearchBackend::Google {
+                engine_id,
+                api_key,
+                quota,
+            } => google(http, params, engine_id, api_key, quota).await,
+            SearchBackend::Searxng { url } => searxng(http, params, url).await,
+            SearchBackend::Brave { api_key } => brave(http, params, api_key).await,
+            SearchBackend::Bing { api_key, endpoint } => {
+                bing(http, params, api_key, endpoint).await
+            }
+        }
+    }
+}
+
+async fn google(
+    http: &HttpClient,
+    params: &SearchParams,
+    engine_id: &str,
+    api_key: &str,
+    quota: &DailyQuota,
+) -> Result<SearchResults, ErrorData> {
+    let builder = http
+        .get("https://customsearch.googleapis.com/customsearch/v1")
+        .query(&params.query_pairs())
+        .query(&[("cx", engine_id), ("key", api_key)]);
+    quota.record();
+    if let Some(warning) = quota.warning() {
+        eprintln!("warning: {}", warning);
+    }
+    // a used up daily quota is reported as 429, which retrying can't fix
+    let res = http
+        .send_quota_limited(builder)
+        .await
+        .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+    if !res.status().is_success() {
+        let status = res.status();
+        let body = res.text().await.unwrap_or_default();
+        return Err(google_error(status, &body));
+    }
+    let results: GoogleSearchResults = res
+        .json()
+        .await
+        .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+    Ok(SearchResults::from_google(results, offset(params)))
+}
+
+#[derive(Deserialize)]
+struct SearxngResponse {
+    results: Vec<SearxngResult>,
+}
+
+#[derive(Deserialize)]
+struct SearxngResult {
+    url: String,
+    title: String,
+    #[serde(default)]
+    content: String,
+    #[serde(rename = "publishedDate")]
+    published_date: Option<String>,
+}
+
+/// SearXNG has no page size, so `start` is mapped onto its ~10 result pages, and results past
+/// `num` are dropped
+async fn searxng(
+    http: &HttpClient,
+    params: &SearchParams,
+    url: &str,
+) -> Result<SearchResults, ErrorData> {
+    let mut query = vec![("q", operator_query(params)), ("format", "json".to_owned())];
+    if let Some(days) = restrict_days(params) {
+        let range = match days {
+            0..=1 => "day",
+            2..=7 => "week",
+            8..=31 => "month",
+            _ => "year",
+        };
+        query.push(("time_range", range.to_owned()));
+    }
+    if let Some(language) = language(params) {
+        query.push(("language", language));
+    }
+    // pages have 10 results, so a start within a page can run short and need the next one too
+    let first_page = offset(params) / 10 + 1;
+    let mut skip = offset(params) % 10;
+    let mut results = Vec::new();
+    for pageno in first_page..first_page + 2 {
+        let mut page_query = query.clone();
+        page_query.push(("pageno", pageno.to_string()));
+        let res = send(
+            http,
+            "SearXNG",
+            http.get(format!("{}/search", url.trim_end_matches('/')))
+                .query(&page_query),
+        )
+        .await?;
+        let response: SearxngResponse = res
+            .json()
+            .await
+            .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+        let count = response.results.len();
+        results.extend(response.results.into_iter().skip(skip));
+        skip = 0;
+        if count == 0 || results.len() >= num(params) {
+            break;
+        }
+    }
+    let items: Vec<_> = results
+        .into_iter()
+        .take(num(params))
+        .map(|result| {
+            (
+                result.title,
+                result.url,
+                result.content,
+                result.published_date,
+            )
+        })
+        .collect();
+    let more = items.len() == num(params);
+    Ok(normalize(params, items, more))
+}
+
+#[derive(Deserialize)]
+struct BraveResponse {
+    web: Option<BraveWeb>,
+    query: Option<BraveQuery>,
+}
+
+#[derive(Deserialize)]
+struct BraveWeb {
+    results: Vec<BraveResult>,
+}
+
+#[derive(Deserialize)]
+struct BraveResult {
+    title: String,
+    url: String,
+    #[serde(default)]
+    description: String,
+    /// e.g. "August 28, 2024" or "2 days ago"
+    age: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct BraveQuery {
+    #[serde(default)]
+    more_results_available: bool,
+}
+
+/// Brave's `offset` counts pages of `count` results, rather than results
+async fn brave(
+    http: &HttpClient,
+    params: &SearchParams,
+    api_key: &str,
+) -> Result<SearchResults, ErrorData> {
+    let count = num(params);
+    let mut query = vec![
+        ("q", operator_query(params)),
+        ("count", count.to_string()),
+        ("offset", (offset(params) / count).to_string()),
+        ("text_decorations", "false".to_owned()),
+    ];
+    if let Some(days) = restrict_days(params) {
+        query.push((
+            "freshness",
+            format!("{}to{}", date_days_ago(days), date_days_ago(0)),
+        ));
+    }
+    if let Some(language) = language(params) {
+        query.push(("search_lang", language));
+    }
+    if let Some(country) = params.gl.as_ref() {
+        query.push(("country", country.to_ascii_uppercase()));
+    }
+    let res = send(
+        http,
+        "Brave",
+        http.get("https://api.search.brave.com/res/v1/web/search")
+            .header("X-Subscription-Token", api_key)
+            .query(&query),
+    )
+    .await?;
+    let response: BraveResponse = res
+        .json()
+        .await
+        .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+    let more = response
+        .query
+        .is_some_and(|query| query.more_results_available);
+    let items = response
+        .web
+        .map(|web| web.results)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|result| (result.title, result.url, result.description, result.age))
+        .collect();
+    Ok(normalize(params, items, more))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BingResponse {
+    web_pages: Option<BingWebPages>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BingWebPages {
+    #[serde(default)]
+    total_estimated_matches: usize,
+    value: Vec<BingResult>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BingResult {
+    name: String,
+    url: String,
+    #[serde(default)]
+    snippet: String,
+    date_published: Option<String>,
+}
+
+async fn bing(
+    http: &HttpClient,
+    params: &SearchParams,
+    api_key: &str,
+    endpoint: &str,
+) -> Result<SearchResults, ErrorData> {
+    let mut query = vec![
+        ("q", operator_query(params)),
+        ("count", num(params).to_string()),
+        ("offset", offset(params).to_string()),
+        ("responseFilter", "Webpages".to_owned()),
+        ("textDecorations", "false".to_owned()),
+    ];
+    if let Some(days) = restrict_days(params) {
+        query.push((
+            "freshness",
+            format!("{}..{}", date_days_ago(days), date_days_ago(0)),
+        ));
+    }
+    if let Some(language) = language(params) {
+        query.push(("setLang", language));
+    }
+    if let Some(country) = params.gl.as_ref() {
+        query.push(("cc", country.clone()));
+    }
+    let res = send(
+        http,
+        "Bing",
+        http.get(endpoint)
+            .header("Ocp-Apim-Subscription-Key", api_key)
+            .query(&query),
+    )
+    .await?;
+    let response: BingResponse = res
+        .json()
+        .await
+        .map_err(|err| ErrorData::internal_error(format!("{}", err), None))?;
+    let Some(pages) = response.web_pages else {
+        return Ok(normalize(params, Vec::new(), false));
+    };
+    let more = offset(params) + pages.value.len() < pages.total_estimated_matches;
+    let items = pages
+        .value
+        .into_iter()
+        .map(|result| {
+            (
+                result.name,
+                result.url,
+                result.snippet,
+                result.date_published,
+            )
+        })
+        .collect();
+    Ok(normalize(params, items, more))
+}
+
+/// Sends a request to a backend other than Google, turning failed responses into errors that
+/// say whether retrying could help
+async fn send(
+    http: &HttpClient,
+    name: &str,
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, ErrorData> {
+    let res = http.send(request).await.map_err(|err| {
+        ErrorData::internal_error(format!("{} search failed: {}", name, err), None)
+    })?;
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    let body: String = res
+        .text()
+        .await
+        .unwrap_or_default()
+        .chars()
+        .take(200)
+        .collect();
+    let message = match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => format!(
+            "{} search is misconfigured on this server and can't be used until it's fixed: {}",
+            name, body
+        ),
+        StatusCode::TOO_MANY_REQUESTS => format!(
+            "{} search is rate limited or out of quota. Do not search again for now",
+            name
+        ),
+        _ => format!("{} search failed ({}): {}", name, status, body),
+    };
+    Err(ErrorData::internal_error(message, None))
+}
+
+/// Builds results from (title, url, snippet, date) tuples
+fn normalize(
+    params: &SearchParams,
+    items: Vec<(String, String, String, Option<String>)>,
+    more: bool,
+) -> SearchResults {
+    let offset = offset(params);
+    let count = items.len();
+    SearchResults {
+        results: items
+            .into_iter()
+            .enumerate()
+            .map(|(index, (title, url, snippet, date))| {
+                SearchResult::new(offset + index + 1, title, url, &strip_tags(&snippet), date)
+            })
+            .collect(),
+        next_start: (more && count > 0 && offset + count < 100).then_some(offset + count + 1),
+    }
+}
+
+/// Other engines take Google's structured parameters as operators in the query itself
+fn operator_query(params: &SearchParams) -> String {
+    let mut query = params.query.clone();
+    if let Some(exact) = params.exact_terms.as_ref() {
+        query.push_str(&format!(" \"{}\"", exact));
+    }
+    if let Some(or_terms) = params.or_terms.as_ref() {
+        let terms: Vec<_> = or_terms.split_whitespace().collect();
+        if !terms.is_empty() {
+            query.push_str(&format!(" ({})", terms.join(" OR ")));
+        }
+    }
+    if let Some(exclude) = params.exclude_terms.as_ref() {
+        for term in exclude.split_whitespace() {
+            query.push_str(&format!(" -{}", term));
+        }
+    }
+    if let Some(site) = params.site_search.as_ref() {
+        match params.site_search_filter {
+            Some(SiteSearchFilter::Exclude) => query.push_str(&format!(" -site:{}", site)),
+            _ => query.push_str(&format!(" site:{}", site)),
+        }
+    }
+    if let Some(file_type) = params.file_type.as_ref() {
+        query.push_str(&format!(" filetype:{}", file_type));
+    }
+    query
+}
+
+/// The index of the first result requested
+fn offset(params: &SearchParams) -> usize {
+    params.start.unwrap_or(1).saturating_sub(1) as usize
+}
+
+fn num(params: &SearchParams) -> usize {
+    params.num.unwrap_or(10) as usize
+}
+
+/// `date_restrict` as a number of days
+fn restrict_days(params: &SearchParams) -> Option<u64> {
+    date_restrict_days(params.date_restrict.as_ref()?)
+}
+
+/// A date restriction such as "w2" as a number of days, or None if it's invalid or too large
+pub fn date_restrict_days(restrict: &str) -> Option<u64> {
+    let count: u64 = restrict.get(1..)?.parse().ok()?;
+    let unit = match restrict.chars().next()? {
+        'd' => 1,
+        'w' => 7,
+        'm' => 31,
+        'y' => 366,
+        _ => return None,
+    };
+    count.checked_mul(unit)
+}
+
+/// The language code of `lr`, e.g. "en" for "lang_en"
+fn language(params: &SearchParams) -> Option<String> {
+    params
+        .lr
+        .as_ref()
+        .map(|lr| lr.strip_prefix("lang_").unwrap_or(lr).to_owned())
+}
+
+/// The UTC date `days` days ago, as YYYY-MM-DD
+fn date_days_ago(days: u64) -> String {
+    let today = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / (24 * 60 * 60);
+    let (year, month, day) = civil_from_days(today.saturating_sub(days) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// Converts days since the Unix epoch to a (year, month, day) in the proleptic Gregorian
+/// calendar, per http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Converts a date in the proleptic Gregorian calendar to days since the Unix epoch, the
+/// inverse of `civil_from_days`
+pub fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::extract::{Query, State};
+
+    use super::*;
+    use crate::{http::RetryPolicy, results::SourceKind};
+
+    #[test]
+    fn test_operator_query() {
+        let params = SearchParams {
+            query: "axum middleware".into(),
+            exact_terms: Some("from_fn".into()),
+            or_terms: Some("tower layer".into()),
+            exclude_terms: Some("actix warp".into()),
+            site_search: Some("docs.rs".into()),
+            site_search_filter: Some(SiteSearchFilter::Exclude),
+            date_restrict: Some("w2".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            operator_query(&params),
+            "axum middleware \"from_fn\" (tower OR layer) -actix -warp -site:docs.rs"
+        );
+        assert_eq!(restrict_days(&params), Some(14));
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19963), (2024, 8, 28));
+    }
+
+    type Queries = Arc<Mutex<Vec<Vec<(String, String)>>>>;
+
+    /// Serves testdata/searxng.json like a SearXNG instance would, recording the queries it got
+    async fn searxng_stand_in() -> (String, Queries) {
+        let queries = Arc::new(Mutex::new(Vec::new()));
+        let router =
+            axum::Router::new()
+                .route(
+                    "/search",
+                    axum::routing::get(
+                        |State(queries): State<Queries>,
+                         Query(query): Query<Vec<(String, String)>>| async move {
+                            queries.lock().unwrap().push(query);
+                            fs::read_to_string("testdata/searxng.json").unwrap()
+                        },
+                    ),
+                )
+                .with_state(queries.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        (url, queries)
+    }
+
+    #[tokio::test]
+    async fn test_searxng_fallback() {
+        let (url, queries) = searxng_stand_in().await;
+        let http = HttpClient::new(
+            "test",
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            RetryPolicy {
+                max_retries: 0,
+                base_delay: Duration::ZERO,
+                max_delay: Duration::ZERO,
+            },
+        )
+        .unwrap();
+        // nothing listens on port 1, so the first backend fails and the stand-in is used
+        let chain = SearchChain::new(vec![
+            SearchBackend::Searxng {
+                url: "http://127.0.0.1:1".into(),
+            },
+            SearchBackend::Searxng { url },
+        ]);
+        assert_eq!(chain.name(None), "searxng+searxng");
+        assert!(!chain.has(BackendKind::Google));
+        let params = SearchParams {
+            query: "tokio select".into(),
+            site_search: Some("docs.rs".into()),
+            date_restrict: Some("m6".into()),
+            num: Some(2),
+            ..Default::default()
+        };
+        let results = chain.search(&http, &params, None).await.unwrap();
+        assert_eq!(results.results.len(), 2);
+        assert_eq!(results.next_start, Some(3));
+        let first = &results.results[0];
+        assert_eq!(first.rank, 1);
+        assert_eq!(first.source, SourceKind::StackOverflow);
+        assert!(!first.snippet.contains("<b>"));
+        assert_eq!(results.results[1].source, SourceKind::Other);
+
+        let query = queries.lock().unwrap()[0].clone();
+        assert!(query.contains(&("pageno".to_owned(), "1".to_owned())));
+        assert!(query.contains(&("q".to_owned(), "tokio select site:docs.rs".to_owned())));
+        assert!(query.contains(&("format".to_owned(), "json".to_owned())));
+        assert!(query.contains(&("time_range".to_owned(), "year".to_owned())));
+
+        // the stand-in's pages have 3 results, so starting at the third runs short of the page
+        queries.lock().unwrap().clear();
+        let params = SearchParams {
+            start: Some(3),
+            ..params
+        };
+        let results = chain.search(&http, &params, None).await.unwrap();
+        assert_eq!(results.results.len(), 2);
+        assert_eq!(results.results[0].rank, 3);
+        assert_eq!(results.next_start, Some(5));
+        let pages: Vec<_> = queries
+            .lock()
+            .unwrap()
+            .iter()
+            .flat_map(|query| query.iter().filter(|(key, _)| key == "pageno"))
+            .map(|(_, page)| page.clone())
+            .collect();
+        assert_eq!(pages, ["1", "2"]);
+
+        let broken = SearchChain::new(vec![
+            SearchBackend::Searxng {
+                url: "http://127.0.0.1:1".into(),
+            },
+            SearchBackend::Searxng {
+                url: "http://127.0.0.1:1".into(),
+            },
+        ]);
+        let error = broken.search(&http, &params, None).await.err().unwrap();
+        assert_eq!(error.message.lines().count(), 2);
+    }
+}
@@ -0,0 +1,125 @@
+/// Threads of popular posts run to hundreds of comments, so they're cut off once this much is
+/// rendered
+pub const MAX_THREAD_CHARS: usize = 40_000;
+
+/// A comment of a discussion thread, e.g. on a Reddit submission, a Discourse topic or a
+/// Hacker News story
+pub struct Comment {
+    /// a short id for the comment, which replies to it refer to. Shorter ids than the sites'
+    /// own help smaller models keep track of who answers whom
+    pub id: usize,
+    pub author: String,
+    /// the id of the comment this one answers, where 0 is the post the thread is on
+    pub parent: Option<usize>,
+    /// e.g. the date and score, or a permalink
+    pub details: String,
+    pub body: String,
+}
+
+/// A post followed by its comments, rendered the same way for every site and cut off once
+/// it exceeds its budget
+pub struct Thread {
+    parts: Vec<String>,
+    /// characters rendered so far
+    used: usize,
+    budget: usize,
+    /// comments that weren't rendered, as the thread was full or they were left out otherwise
+    left_out: usize,
+    /// set once a comment didn't fit, after which nothing more is added
+    full: bool,
+}
+
+impl Thread {
+    /// Starts a thread with the post it's on, which counts towards `budget`
+    pub fn new(post: String, budget: usize) -> Self {
+        Self {
+            used: post.chars().count(),
+            parts: vec![post],
+            budget,
+            left_out: 0,
+            full: false,
+        }
+    }
+
+    /// Adds a comment, unless it doesn't fit in the budget. Returns whether it was added
+    pub fn push(&mut self, comment: Comment) -> bool {
+        if self.full {
+            self.left_out += 1;
+            return false;
+        }
+        let response_to = match comment.parent {
+            Some(parent) => format!(" In response to: {}", parent),
+            None => String::new(),
+        };
+        let details = match comment.details.as_str() {
+            "" => String::new(),
+            details => format!("<p>{}</p>", details),
+        };
+        let part = format!(
+            "<h1>Comment: #{} from {}{}</h1>{}<p>{}</p>",
+            comment.id, comment.author, response_to, details, comment.body
+        );
+        let length = part.chars().count();
+        if self.used + length > self.budget {
+            self.full = true;
+            self.left_out += 1;
+            return false;
+        }
+        self.used += length;
+        self.parts.push(part);
+        true
+    }
+
+    /// Counts comments that are left out for other reasons, e.g. being nested too deep
+    pub fn leave_out(&mut self, count: usize) {
+        self.left_out += count;
+    }
+
+    /// The post and comments, noting how many comments were left out
+    pub fn into_parts(mut self) -> Vec<String> {
+        if self.left_out > 0 {
+            self.parts
+                .push(format!("({} more comments left out)", self.left_out));
+        }
+        self.parts
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn comment(id: usize, parent: Option<usize>, body: &str) -> Comment {
+        Comment {
+            id,
+            author: "ferris".to_owned(),
+            parent,
+            details: String::new(),
+            body: body.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_thread() {
+        let mut thread = Thread::new("# Post".to_owned(), 150);
+        assert!(thread.push(comment(1, Some(0), "first")));
+        assert!(thread.push(Comment {
+            details: "2024-03-01, 5 likes".to_owned(),
+            ..comment(2, None, "second")
+        }));
+        assert!(!thread.push(comment(3, Some(1), &"long ".repeat(20))));
+        // once full, shorter comments are left out too, so the thread doesn't skip replies
+        assert!(!thread.push(comment(4, Some(1), "short")));
+        thread.leave_out(2);
+        let parts = thread.into_parts();
+        assert_eq!(
+            parts,
+            [
+                "# Post",
+                "<h1>Comment: #1 from ferris In response to: 0</h1><p>first</p>",
+                "<h1>Comment: #2 from ferris</h1><p>2024-03-01, 5 likes</p><p>second</p>",
+                "(4 more comments left out)"
+            ]
+        );
+    }
+}